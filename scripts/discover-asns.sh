@@ -11,6 +11,9 @@
 #
 # The script will restart your docker-compose stack multiple times to connect
 # to different VPN servers and collect their ASNs.
+#
+# Prefer `gluetun-monitor discover-asns`, which reconnects the VPN through the
+# Gluetun control API instead of restarting the stack.
 
 set -euo pipefail
 
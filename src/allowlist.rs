@@ -0,0 +1,274 @@
+// Allowlist Module
+//!
+//! Holds the set of allowed ASNs shared by handlers and background tasks.
+//!
+//! The set can be replaced at runtime (e.g. on configuration reload). Readers
+//! take a cheap snapshot, so a swap is atomic: every check sees either the old
+//! or the new list, never a mix.
+//!
+//! [`AsnVerdict`] classifies an ASN against the allowlist and the home ISP
+//! deny-list, which takes precedence.
+//!
+//! The admin API records its changes as [`AllowlistOverrides`] on top of the
+//! configured list, so they survive a reload and can be persisted to disk.
+//! They are saved after the write lock is released, so `/check` never waits for
+//! the disk; each change carries a version and a save never overwrites a newer one.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt, fs,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+use tokio::sync::Mutex;
+
+/// Runtime-replaceable set of allowed ASNs
+pub struct AllowList {
+    inner: RwLock<Inner>,
+    /// Version of the overrides last saved, held while saving so saves stay in order
+    saved: Mutex<u64>,
+}
+
+struct Inner {
+    /// ASNs from the configuration
+    base: HashSet<String>,
+    overrides: AllowlistOverrides,
+    /// `base` with `overrides` applied
+    effective: Arc<HashSet<String>>,
+    /// Incremented on every change
+    version: u64,
+}
+
+/// ASNs added and removed at runtime through the admin API
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllowlistOverrides {
+    #[serde(default)]
+    pub added: BTreeSet<String>,
+    #[serde(default)]
+    pub removed: BTreeSet<String>,
+}
+
+/// How an ASN compares to the allow and deny lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsnVerdict {
+    /// In the allowlist
+    Allowed,
+    /// In the home ISP deny-list: traffic is leaking outside the VPN
+    Denied,
+    /// In neither list, or unknown
+    Unknown,
+}
+
+impl AsnVerdict {
+    /// Classify an ASN, the deny-list wins over the allowlist
+    pub fn of(asn: Option<&str>, allowed: &HashSet<String>, denied: &HashSet<String>) -> Self {
+        match asn {
+            Some(asn) if denied.contains(asn) => AsnVerdict::Denied,
+            Some(asn) if allowed.contains(asn) => AsnVerdict::Allowed,
+            _ => AsnVerdict::Unknown,
+        }
+    }
+}
+
+/// ASNs added and removed by an allowlist update
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AsnDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl AllowList {
+    pub fn new(asns: HashSet<String>) -> Self {
+        Self::with_overrides(asns, AllowlistOverrides::default())
+    }
+
+    /// Configured ASNs with runtime overrides applied on top
+    pub fn with_overrides(asns: HashSet<String>, overrides: AllowlistOverrides) -> Self {
+        let effective = Arc::new(overrides.apply(&asns));
+        Self {
+            inner: RwLock::new(Inner {
+                base: asns,
+                overrides,
+                effective,
+                version: 0,
+            }),
+            saved: Mutex::new(0),
+        }
+    }
+
+    /// Current allowlist, unaffected by later updates
+    pub fn snapshot(&self) -> Arc<HashSet<String>> {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .effective
+            .clone()
+    }
+
+    /// Whether the ASN is currently allowed
+    pub fn contains(&self, asn: &str) -> bool {
+        self.snapshot().contains(asn)
+    }
+
+    /// Whether the allowlist is currently empty
+    pub fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+
+    /// Current allowlist as a sorted vector
+    pub fn sorted(&self) -> Vec<String> {
+        let mut asns: Vec<String> = self.snapshot().iter().cloned().collect();
+        asns.sort();
+        asns
+    }
+
+    /// Current runtime overrides
+    pub fn overrides(&self) -> AllowlistOverrides {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .overrides
+            .clone()
+    }
+
+    /// Replace the configured allowlist, returning what changed
+    ///
+    /// Runtime overrides are kept and applied to the new list.
+    pub fn replace(&self, asns: HashSet<String>) -> AsnDiff {
+        // Nothing to save, the overrides are unchanged
+        self.update(|inner| inner.base = asns).0
+    }
+
+    /// Allow ASNs at runtime, returning what changed
+    ///
+    /// With `state_file`, the overrides are saved to it; the change is kept
+    /// even if saving fails.
+    pub async fn add(&self, asns: &[String], state_file: Option<&Path>) -> Result<AsnDiff, String> {
+        let (diff, overrides, version) = self.update(|inner| {
+            for asn in asns {
+                inner.overrides.removed.remove(asn);
+                if !inner.base.contains(asn) {
+                    inner.overrides.added.insert(asn.clone());
+                }
+            }
+        });
+        self.persist(state_file, overrides, version).await?;
+        Ok(diff)
+    }
+
+    /// Disallow an ASN at runtime, returning what changed
+    ///
+    /// With `state_file`, the overrides are saved to it; the change is kept
+    /// even if saving fails.
+    pub async fn remove(&self, asn: &str, state_file: Option<&Path>) -> Result<AsnDiff, String> {
+        let (diff, overrides, version) = self.update(|inner| {
+            inner.overrides.added.remove(asn);
+            if inner.base.contains(asn) {
+                inner.overrides.removed.insert(asn.to_string());
+            }
+        });
+        self.persist(state_file, overrides, version).await?;
+        Ok(diff)
+    }
+
+    /// Apply a change and swap in the recomputed allowlist, returning the
+    /// resulting overrides and their version for saving
+    fn update(&self, change: impl FnOnce(&mut Inner)) -> (AsnDiff, AllowlistOverrides, u64) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        change(&mut inner);
+
+        let effective = inner.overrides.apply(&inner.base);
+        let diff = AsnDiff::between(&inner.effective, &effective);
+        inner.effective = Arc::new(effective);
+        inner.version += 1;
+        (diff, inner.overrides.clone(), inner.version)
+    }
+
+    /// Save overrides unless a newer version already reached the state file
+    async fn persist(
+        &self,
+        state_file: Option<&Path>,
+        overrides: AllowlistOverrides,
+        version: u64,
+    ) -> Result<(), String> {
+        let Some(path) = state_file else {
+            return Ok(());
+        };
+        let mut saved = self.saved.lock().await;
+        if *saved < version {
+            overrides.save(path).await?;
+            *saved = version;
+        }
+        Ok(())
+    }
+}
+
+impl AllowlistOverrides {
+    /// Read overrides from a JSON state file, empty if the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("invalid allowlist state in {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Write overrides to a JSON state file, replacing it atomically
+    pub async fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("tmp");
+        let written = match tokio::fs::write(&tmp, contents).await {
+            Ok(()) => tokio::fs::rename(&tmp, path).await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    /// True when no runtime changes were made
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    fn apply(&self, base: &HashSet<String>) -> HashSet<String> {
+        base.iter()
+            .chain(&self.added)
+            .filter(|asn| !self.removed.contains(*asn))
+            .cloned()
+            .collect()
+    }
+}
+
+impl AsnDiff {
+    /// Compute the ASNs added and removed going from `old` to `new`
+    pub fn between(old: &HashSet<String>, new: &HashSet<String>) -> Self {
+        let mut added: Vec<String> = new.difference(old).cloned().collect();
+        let mut removed: Vec<String> = old.difference(new).cloned().collect();
+        added.sort();
+        removed.sort();
+        Self { added, removed }
+    }
+
+    /// True when nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for AsnDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+
+        let mut parts = Vec::with_capacity(2);
+        if !self.added.is_empty() {
+            parts.push(format!("added {}", self.added.join(", ")));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("removed {}", self.removed.join(", ")));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
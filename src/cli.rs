@@ -0,0 +1,121 @@
+// CLI Module
+//!
+//! Parses command-line arguments.
+//!
+//! ## Options
+//! - `--config <PATH>`: TOML or YAML config file (also `MONITOR_CONFIG`)
+//! - `--check-config`: Validate the configuration and exit (non-zero on errors)
+//! - `--help`: Print usage and exit
+//!
+//! ## Commands
+//! - `discover-asns [--iterations <N>] [--timeout <SECS>]`: Cycle the VPN through the
+//!   Gluetun control API and print the ASNs seen as a `VPN_ALLOWED_ASNS` line
+
+use std::{env, path::PathBuf};
+
+/// Usage text printed by `--help`
+pub const USAGE: &str = "Usage: gluetun-monitor [--config <PATH>] [--check-config]
+       gluetun-monitor [--config <PATH>] discover-asns [--iterations <N>] [--timeout <SECS>]
+
+Options:
+  --config <PATH>  TOML or YAML config file (env: MONITOR_CONFIG)
+  --check-config   Validate the configuration and exit (non-zero on errors)
+  -h, --help       Print this help
+
+Commands:
+  discover-asns    Restart the VPN through the Gluetun API (GLUETUN_API_URL) and
+                   print every ASN seen as a VPN_ALLOWED_ASNS line
+    --iterations <N>   Number of reconnects (default: 20)
+    --timeout <SECS>   Seconds to wait for a new public IP per reconnect (default: 300)
+
+Environment variables override values from the config file.";
+
+/// Default number of reconnects for `discover-asns`
+pub const DEFAULT_DISCOVER_ITERATIONS: u32 = 20;
+
+/// Default seconds to wait for a new public IP after each reconnect
+pub const DEFAULT_DISCOVER_TIMEOUT_SECS: u64 = 300;
+
+/// What the process should do
+#[derive(Debug, Default, PartialEq)]
+pub enum Command {
+    /// Run the monitoring server
+    #[default]
+    Serve,
+    /// Cycle the VPN and collect ASNs
+    DiscoverAsns { iterations: u32, timeout_secs: u64 },
+}
+
+/// Parsed command-line arguments
+#[derive(Debug, Default, PartialEq)]
+pub struct Cli {
+    pub config_path: Option<PathBuf>,
+    pub check_config: bool,
+    pub help: bool,
+    pub command: Command,
+}
+
+impl Cli {
+    /// Parse the process arguments, falling back to `MONITOR_CONFIG` for the config path
+    pub fn parse() -> Result<Self, String> {
+        let mut cli = Self::parse_from(env::args().skip(1))?;
+        if cli.config_path.is_none() {
+            cli.config_path = env::var("MONITOR_CONFIG")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(PathBuf::from);
+        }
+        Ok(cli)
+    }
+
+    /// Parse the given arguments (without the program name)
+    pub fn parse_from<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match (arg.as_str(), &mut cli.command) {
+                ("-h" | "--help", _) => cli.help = true,
+                ("--check-config", _) => cli.check_config = true,
+                ("--config", _) => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| "--config requires a path".to_string())?;
+                    cli.config_path = Some(PathBuf::from(path));
+                }
+                ("discover-asns", Command::Serve) => {
+                    cli.command = Command::DiscoverAsns {
+                        iterations: DEFAULT_DISCOVER_ITERATIONS,
+                        timeout_secs: DEFAULT_DISCOVER_TIMEOUT_SECS,
+                    };
+                }
+                ("--iterations", Command::DiscoverAsns { iterations, .. }) => {
+                    *iterations = parse_number("--iterations", args.next())?;
+                    if *iterations == 0 {
+                        return Err("--iterations must be at least 1".to_string());
+                    }
+                }
+                ("--timeout", Command::DiscoverAsns { timeout_secs, .. }) => {
+                    *timeout_secs = parse_number("--timeout", args.next())?;
+                }
+                (other, _) => match other.strip_prefix("--config=") {
+                    Some(path) => cli.config_path = Some(PathBuf::from(path)),
+                    None => return Err(format!("unknown argument '{}'", other)),
+                },
+            }
+        }
+
+        Ok(cli)
+    }
+}
+
+/// Parse the numeric value following an option
+fn parse_number<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} requires a number", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("{}: '{}' is not a valid number", option, value))
+}
@@ -1,62 +1,1274 @@
 // Configuration Module
 //!
-//! Handles loading and parsing of application configuration from environment variables.
+//! Handles loading and parsing of application configuration from an optional
+//! TOML/YAML file and environment variables.
+//!
+//! ## Precedence
+//! 1. Environment variables (highest)
+//! 2. Config file given with `--config` or `MONITOR_CONFIG`
+//! 3. Built-in defaults
 //!
 //! ## Environment Variables
-//! - `VPN_ALLOWED_ASNS`: Comma-separated list of allowed ASNs (required)
+//! - `VPN_ALLOWED_ASNS`: Comma-separated list of allowed ASNs (required unless `VPN_ALLOWED_CIDRS` is set)
+//! - `VPN_DENIED_ASNS`: Comma-separated home ISP ASNs, seeing one is reported as a leak (optional)
+//! - `VPN_ALLOWED_CIDRS`: Comma-separated exit IP ranges, IPv4 or IPv6 (optional, can replace `VPN_ALLOWED_ASNS`)
+//! - `VPN_ALLOWED_COUNTRIES` / `VPN_DENIED_COUNTRIES`: Comma-separated exit countries (optional)
+//! - `VPN_ALLOWED_REGIONS` / `VPN_DENIED_REGIONS`: Comma-separated exit regions (optional)
+//! - `VPN_ALLOWED_CITIES` / `VPN_DENIED_CITIES`: Comma-separated exit cities (optional)
 //! - `GLUETUN_API_URL`: Gluetun API endpoint (optional)
 //! - `GLUETUN_API_KEY`: Gluetun API key (optional)
-//! - `NTFY_URL`: ntfy.sh notification URL (optional)
+//! - `NTFY_URL`: ntfy.sh notification URL (optional, replaces `[[notifiers]]` from the file)
 //! - `NTFY_INTERVAL_HOURS`: Notification interval in hours (default: 2, min: 1)
+//! - `NTFY_ON_RELOAD`: Notify when a reload changes the ASN allowlist (default: false)
 //! - `VPN_CHECK_INTERVAL_MINUTES`: VPN check interval in minutes (default: 5, min: 1)
+//! - `IP_LOOKUP_PROVIDERS`: Comma-separated provider chain, tried in order
+//!   (default: `gluetun,ifconfig,ipapi`, `gluetun` only if `GLUETUN_API_URL` is set)
+//! - `IP_LOOKUP_MODE`: `fallback` to try providers in order, `race` to query all concurrently
+//!   and take the first answer by chain order, `consensus` to query all and compare their
+//!   answers (default: `fallback`)
+//! - `IP_LOOKUP_DUAL_STACK`: Look up the IPv4 and IPv6 exit separately, enabling the
+//!   `ipv6_leak` rule (default: false)
+//! - `IPV6_LOOKUP_PROVIDERS`: Comma-separated IPv6 provider chain (default: `ifconfig,ipapi`)
+//! - `IP_LOOKUP_PROXY`: Send the provider chain through this `http://`, `https://`, `socks5://`
+//!   or `socks5h://` proxy, e.g. Gluetun's HTTP proxy (optional, default chain then skips `gluetun`)
+//! - `IP_LOOKUP_TIMEOUTS`: Comma-separated `provider=seconds` timeouts, `default=seconds`
+//!   for the rest (optional, replaces `[lookup.timeouts]` from the file)
+//! - `MONITOR_BIND_ADDR`: IPv4 or IPv6 address to listen on (default: `0.0.0.0`)
+//! - `MONITOR_PORT`: TCP port to listen on (default: 3010)
+//! - `MONITOR_UNIX_SOCKET`: Additional Unix domain socket path to listen on (optional)
+//! - `LOOKUP_CACHE_TTL_SECONDS`: How long a lookup result is shared (default: 30, 0 disables)
+//! - `ASN_DATABASE`: Local iptoasn TSV or MaxMind `.mmdb` file for the `offline` provider
+//!   and the `asn_crosscheck` rule (optional)
+//! - `ASN_LEARN_WINDOW_HOURS`: Record observed ASNs for `/asns/observed` over this window (default: 0, disabled)
+//! - `ADMIN_TOKEN`: Bearer token enabling the `/admin/asns` API (optional)
+//! - `ADMIN_STATE_FILE`: JSON file persisting admin API allowlist changes (optional)
+//! - `DNS_LEAK_CHECK`: Probe which DNS resolvers answer, shown in `/status` (default: false)
+//! - `DNS_WHOAMI_NAME`: Name answered with the resolver's address (default: `whoami.akamai.net`)
+//! - `DNS_PROBE_NAMES`: Comma-separated extra names that must resolve (optional)
+//! - `DNS_SERVER`: Query this `IP[:port]` over UDP instead of the system resolver (optional)
+//! - `DNS_ALLOWED_RESOLVERS`: Comma-separated resolver IPs or CIDRs (optional)
+//! - `DNS_ALLOWED_RESOLVER_ASNS`: Comma-separated resolver ASNs, `VPN_ALLOWED_ASNS` if neither is set
+//! - `HOME_IPS`: Comma-separated home (non-VPN) IPs or CIDRs Gluetun's exit must differ from (optional)
+//! - `HOME_LOOKUP_URL`: Lookup URL reachable outside the tunnel, answering the home IP (optional)
+//! - `KILL_SWITCH_PROBE_INTERVAL_HOURS`: Stop the tunnel and check that traffic is blocked
+//!   every N hours (default: 0, disabled)
+//! - `KILL_SWITCH_PROBE_URL`: URL that must be unreachable while the tunnel is stopped
+//!   (default: `https://1.1.1.1/cdn-cgi/trace`)
+//! - `CHECK_RULES`: Comma-separated `/check` rules, `name` or `name:warn` (default: the configured checks)
+//!
+//! Every variable can instead be read from a file by appending `_FILE`
+//! (e.g. `GLUETUN_API_KEY_FILE=/run/secrets/gluetun_api_key`), for Docker/Kubernetes secrets.
+//!
+//! ASN labels can only be set in the config file.
+//!
+//! ## Validation
+//! [`Config::validate`] reports errors (unparsable values, malformed ASNs or URLs,
+//! empty allowlist, unknown providers) and warnings (clamped or unused values).
+//! Startup aborts on errors; `--check-config` prints the report and exits.
 
-use std::{collections::HashSet, env};
+use crate::{
+    dns::{self, DnsAllowList},
+    geofence::{Geofence, LocationRule},
+    ip_lookup::{self, LookupMode},
+    killswitch,
+    policy::{self, Policy, RuleKind},
+};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-/// Application configuration loaded from environment variables
+/// Application configuration merged from the config file and environment variables
 pub struct Config {
     pub allowed_asns: HashSet<String>,
-    pub ntfy_url: Option<String>,
+    pub denied_asns: HashSet<String>,
+    pub asn_labels: HashMap<String, String>,
+    pub geofence: Geofence,
+    pub ntfy_urls: Vec<String>,
     pub gluetun_url: Option<String>,
     pub gluetun_api_key: Option<String>,
     pub notification_interval_hours: u64,
+    pub notify_on_reload: bool,
     pub check_interval_minutes: u64,
+    pub ip_lookup_providers: Vec<String>,
+    pub provider_timeouts: HashMap<String, u64>,
+    pub lookup_mode: LookupMode,
+    /// Separate IPv4 and IPv6 lookups
+    pub dual_stack: bool,
+    pub ipv6_lookup_providers: Vec<String>,
+    /// Proxy the provider chain goes through
+    pub lookup_proxy: Option<String>,
+    pub lookup_cache_ttl_seconds: u64,
+    /// Local IP-to-ASN database file
+    pub asn_database: Option<PathBuf>,
+    pub bind_addr: IpAddr,
+    pub port: u16,
+    pub unix_socket: Option<PathBuf>,
+    pub admin_token: Option<String>,
+    pub admin_state_file: Option<PathBuf>,
+    pub learn_window_hours: u64,
+    /// `/check` rule specs, empty for the default rules
+    pub check_rules: Vec<String>,
+    /// Exit IP ranges, checked by the `ip_cidr` rule
+    pub allowed_cidrs: Vec<IpNet>,
+    /// Run the DNS leak probe with every lookup
+    pub dns_leak_check: bool,
+    pub dns_whoami: String,
+    pub dns_probe_names: Vec<String>,
+    /// Server queried directly instead of the system resolver
+    pub dns_server: Option<SocketAddr>,
+    /// Resolvers accepted by the `dns_leak` rule
+    pub dns_allowed: DnsAllowList,
+    /// Home (non-VPN) IPs and ranges, checked against Gluetun's exit IP
+    pub home_ips: Vec<IpNet>,
+    pub home_lookup_url: Option<String>,
+    /// Hours between tunnel-stop probes, 0 disables them
+    pub kill_switch_probe_interval_hours: u64,
+    pub kill_switch_probe_url: String,
+    /// Problems found while reading values, merged into [`Config::validate`]
+    parse_report: ConfigReport,
+}
+
+/// Errors and warnings found by configuration validation
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ConfigReport {
+    /// True when there are no errors (warnings are allowed)
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Structure of the optional config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub vpn: VpnSection,
+    pub geofence: GeofenceSection,
+    pub gluetun: GluetunSection,
+    pub notifications: NotificationsSection,
+    pub notifiers: Vec<NotifierSection>,
+    pub lookup: LookupSection,
+    pub server: ServerSection,
+    pub admin: AdminSection,
+    pub learn: LearnSection,
+    pub check: CheckSection,
+    pub dns: DnsSection,
+    pub kill_switch: KillSwitchSection,
+}
+
+/// `[vpn]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VpnSection {
+    pub allowed_asns: Vec<AsnEntry>,
+    /// Home ISP ASNs, seeing one means traffic is leaking outside the VPN
+    pub denied_asns: Vec<AsnEntry>,
+    /// Exit IP ranges (`10.0.0.0/8`, `2001:db8::/32` or a single address)
+    pub allowed_cidrs: Vec<String>,
+    pub check_interval_minutes: Option<u64>,
+}
+
+/// Allowed ASN, either plain (`"AS12345"`) or labeled (`{ asn = "AS12345", label = "..." }`)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AsnEntry {
+    Plain(String),
+    Labeled { asn: String, label: Option<String> },
+}
+
+/// `[geofence]` section, names as reported by the lookup provider
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeofenceSection {
+    pub allowed_countries: Vec<String>,
+    pub denied_countries: Vec<String>,
+    pub allowed_regions: Vec<String>,
+    pub denied_regions: Vec<String>,
+    pub allowed_cities: Vec<String>,
+    pub denied_cities: Vec<String>,
+}
+
+/// `[gluetun]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GluetunSection {
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+}
+
+/// `[notifications]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsSection {
+    pub interval_hours: Option<u64>,
+    pub on_reload: Option<bool>,
+}
+
+/// One `[[notifiers]]` entry
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifierSection {
+    pub url: String,
+}
+
+/// `[lookup]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LookupSection {
+    pub providers: Vec<String>,
+    /// `fallback`, `race` or `consensus`
+    pub mode: Option<String>,
+    pub dual_stack: Option<bool>,
+    pub ipv6_providers: Vec<String>,
+    /// `http://`, `https://`, `socks5://` or `socks5h://` proxy URL
+    pub proxy: Option<String>,
+    pub cache_ttl_seconds: Option<u64>,
+    /// Per-provider timeout in seconds, keyed by provider name
+    pub timeouts: HashMap<String, u64>,
+    pub asn_database: Option<PathBuf>,
+}
+
+/// `[server]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    pub bind_addr: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub unix_socket: Option<PathBuf>,
+}
+
+/// `[admin]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminSection {
+    pub token: Option<String>,
+    pub state_file: Option<PathBuf>,
+}
+
+/// `[learn]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LearnSection {
+    pub window_hours: Option<u64>,
+}
+
+/// `[check]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckSection {
+    pub rules: Vec<String>,
+}
+
+/// `[dns]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsSection {
+    pub leak_check: Option<bool>,
+    pub whoami: Option<String>,
+    pub names: Vec<String>,
+    /// `IP` or `IP:port`
+    pub server: Option<String>,
+    /// Resolver IPs or CIDRs
+    pub allowed_resolvers: Vec<String>,
+    pub allowed_resolver_asns: Vec<String>,
+}
+
+/// `[kill_switch]` section
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KillSwitchSection {
+    /// Home IPs or CIDRs
+    pub home_ips: Vec<String>,
+    pub home_lookup_url: Option<String>,
+    pub probe_interval_hours: Option<u64>,
+    pub probe_url: Option<String>,
+}
+
+impl FileConfig {
+    /// Read a config file, the format is chosen by extension (`.yaml`/`.yml`, otherwise TOML)
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+        let is_yaml = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+
+        if is_yaml {
+            serde_norway::from_str(&contents)
+                .map_err(|e| format!("invalid YAML in {}: {}", path.display(), e))
+        } else {
+            toml::from_str(&contents)
+                .map_err(|e| format!("invalid TOML in {}: {}", path.display(), e))
+        }
+    }
 }
 
 impl Config {
     /// Load configuration from environment variables
     pub fn from_env() -> Self {
-        let allowed_env = env::var("VPN_ALLOWED_ASNS").unwrap_or_default();
-        let allowed_asns: HashSet<String> = allowed_env
-            .split(',')
-            .map(|s| s.trim().to_uppercase())
-            .filter(|s| !s.is_empty())
-            .collect();
+        Self::from_file_and_env(FileConfig::default())
+    }
 
-        let ntfy_url = env::var("NTFY_URL").ok();
-        let gluetun_url = env::var("GLUETUN_API_URL").ok();
-        let gluetun_api_key = env::var("GLUETUN_API_KEY").ok();
+    /// Load configuration from an optional config file, overridden by environment variables
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let file = match path {
+            Some(path) => FileConfig::load(path)?,
+            None => FileConfig::default(),
+        };
+        Ok(Self::from_file_and_env(file))
+    }
+
+    /// Merge file values with environment variables, environment wins
+    pub fn from_file_and_env(file: FileConfig) -> Self {
+        let mut report = ConfigReport::default();
+        let mut asn_labels = HashMap::new();
+        let allowed_asns = match env_var("VPN_ALLOWED_ASNS", &mut report) {
+            Some(allowed_env) => parse_asn_list(&allowed_env),
+            None => parse_asn_entries(file.vpn.allowed_asns, &mut asn_labels),
+        };
+        let denied_asns = match env_var("VPN_DENIED_ASNS", &mut report) {
+            Some(denied_env) => parse_asn_list(&denied_env),
+            None => parse_asn_entries(file.vpn.denied_asns, &mut asn_labels),
+        };
+
+        let mut location_list =
+            |name: &str, file_value: Vec<String>| match env_var(name, &mut report) {
+                Some(list) => list.split(',').map(str::to_string).collect(),
+                None => file_value,
+            };
+        let geofence = Geofence {
+            countries: LocationRule::countries(
+                location_list("VPN_ALLOWED_COUNTRIES", file.geofence.allowed_countries),
+                location_list("VPN_DENIED_COUNTRIES", file.geofence.denied_countries),
+            ),
+            regions: LocationRule::new(
+                location_list("VPN_ALLOWED_REGIONS", file.geofence.allowed_regions),
+                location_list("VPN_DENIED_REGIONS", file.geofence.denied_regions),
+            ),
+            cities: LocationRule::new(
+                location_list("VPN_ALLOWED_CITIES", file.geofence.allowed_cities),
+                location_list("VPN_DENIED_CITIES", file.geofence.denied_cities),
+            ),
+        };
+
+        let ntfy_urls = match env_var("NTFY_URL", &mut report) {
+            Some(url) => vec![url],
+            None => file.notifiers.into_iter().map(|n| n.url).collect(),
+        };
+        let gluetun_url = env_var("GLUETUN_API_URL", &mut report).or(file.gluetun.api_url);
+        let gluetun_api_key = env_var("GLUETUN_API_KEY", &mut report).or(file.gluetun.api_key);
 
         // Parse notification interval, default to 2 hours, minimum 1 hour
-        let notification_interval_hours = env::var("NTFY_INTERVAL_HOURS")
-            .ok()
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(2)
-            .max(1); // Ensure at least 1 hour
+        let notification_interval_hours = env_parse::<u64>("NTFY_INTERVAL_HOURS", &mut report)
+            .or(file.notifications.interval_hours)
+            .unwrap_or(2);
+        if notification_interval_hours == 0 {
+            report
+                .warnings
+                .push("NTFY_INTERVAL_HOURS is 0, using the minimum of 1".to_string());
+        }
+        let notification_interval_hours = notification_interval_hours.max(1); // Ensure at least 1 hour
+
+        let notify_on_reload = env_bool("NTFY_ON_RELOAD", &mut report)
+            .or(file.notifications.on_reload)
+            .unwrap_or(false);
 
         // Parse check interval, default to 5 minutes, minimum 1 minute
-        let check_interval_minutes = env::var("VPN_CHECK_INTERVAL_MINUTES")
-            .ok()
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(5)
-            .max(1); // Ensure at least 1 minute
+        let check_interval_minutes = env_parse::<u64>("VPN_CHECK_INTERVAL_MINUTES", &mut report)
+            .or(file.vpn.check_interval_minutes)
+            .unwrap_or(5);
+        if check_interval_minutes == 0 {
+            report
+                .warnings
+                .push("VPN_CHECK_INTERVAL_MINUTES is 0, using the minimum of 1".to_string());
+        }
+        let check_interval_minutes = check_interval_minutes.max(1); // Ensure at least 1 minute
+
+        // Parse lookup cache TTL, default to 30 seconds
+        let lookup_cache_ttl_seconds = env_parse::<u64>("LOOKUP_CACHE_TTL_SECONDS", &mut report)
+            .or(file.lookup.cache_ttl_seconds)
+            .unwrap_or(30);
+
+        let asn_database = env_var("ASN_DATABASE", &mut report)
+            .map(PathBuf::from)
+            .or(file.lookup.asn_database);
+
+        // Parse listen address, brackets are accepted for IPv6 (e.g. "[::]")
+        let bind_addr = env_var("MONITOR_BIND_ADDR", &mut report)
+            .and_then(|s| {
+                let parsed = s
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .ok();
+                if parsed.is_none() {
+                    report.errors.push(format!(
+                        "MONITOR_BIND_ADDR: '{}' is not an IPv4 or IPv6 address",
+                        s
+                    ));
+                }
+                parsed
+            })
+            .or(file.server.bind_addr)
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        let port = env_parse::<u16>("MONITOR_PORT", &mut report)
+            .or(file.server.port)
+            .unwrap_or(3010);
+
+        let unix_socket = env_var("MONITOR_UNIX_SOCKET", &mut report)
+            .map(PathBuf::from)
+            .or(file.server.unix_socket);
+
+        let admin_token = env_var("ADMIN_TOKEN", &mut report).or(file.admin.token);
+        let admin_state_file = env_var("ADMIN_STATE_FILE", &mut report)
+            .map(PathBuf::from)
+            .or(file.admin.state_file);
+
+        // Learn mode is off unless a window is set
+        let learn_window_hours = env_parse::<u64>("ASN_LEARN_WINDOW_HOURS", &mut report)
+            .or(file.learn.window_hours)
+            .unwrap_or(0);
+
+        let check_rules: Vec<String> = match env_var("CHECK_RULES", &mut report) {
+            Some(list) => list
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            None => file.check.rules,
+        };
+
+        let cidrs: Vec<String> = match env_var("VPN_ALLOWED_CIDRS", &mut report) {
+            Some(list) => list
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            None => file.vpn.allowed_cidrs,
+        };
+        let allowed_cidrs = cidrs
+            .iter()
+            .filter_map(|cidr| match policy::parse_cidr(cidr) {
+                Ok(net) => Some(net),
+                Err(e) => {
+                    report.errors.push(format!("VPN_ALLOWED_CIDRS: {}", e));
+                    None
+                }
+            })
+            .collect();
+
+        let dns_leak_check = env_bool("DNS_LEAK_CHECK", &mut report)
+            .or(file.dns.leak_check)
+            .unwrap_or(false);
+        let dns_whoami = env_var("DNS_WHOAMI_NAME", &mut report)
+            .or(file.dns.whoami)
+            .unwrap_or_else(|| dns::DEFAULT_WHOAMI.to_string());
+        let dns_probe_names = match env_var("DNS_PROBE_NAMES", &mut report) {
+            Some(list) => split_list(&list),
+            None => file.dns.names,
+        };
+        let dns_server = env_var("DNS_SERVER", &mut report)
+            .or(file.dns.server)
+            .and_then(|server| {
+                dns::parse_server(&server)
+                    .map_err(|e| report.errors.push(format!("DNS_SERVER: {}", e)))
+                    .ok()
+            });
+        let resolvers = match env_var("DNS_ALLOWED_RESOLVERS", &mut report) {
+            Some(list) => split_list(&list),
+            None => file.dns.allowed_resolvers,
+        };
+        let dns_allowed = DnsAllowList {
+            resolvers: resolvers
+                .iter()
+                .filter_map(|cidr| {
+                    policy::parse_cidr(cidr)
+                        .map_err(|e| report.errors.push(format!("DNS_ALLOWED_RESOLVERS: {}", e)))
+                        .ok()
+                })
+                .collect(),
+            asns: match env_var("DNS_ALLOWED_RESOLVER_ASNS", &mut report) {
+                Some(list) => parse_asn_list(&list),
+                None => file
+                    .dns
+                    .allowed_resolver_asns
+                    .iter()
+                    .map(|asn| asn.trim().to_uppercase())
+                    .filter(|asn| !asn.is_empty())
+                    .collect(),
+            },
+        };
+
+        let home_ips = match env_var("HOME_IPS", &mut report) {
+            Some(list) => split_list(&list),
+            None => file.kill_switch.home_ips,
+        }
+        .iter()
+        .filter_map(|ip| {
+            policy::parse_cidr(ip)
+                .map_err(|e| report.errors.push(format!("HOME_IPS: {}", e)))
+                .ok()
+        })
+        .collect();
+        let home_lookup_url =
+            env_var("HOME_LOOKUP_URL", &mut report).or(file.kill_switch.home_lookup_url);
+        let kill_switch_probe_interval_hours =
+            env_parse::<u64>("KILL_SWITCH_PROBE_INTERVAL_HOURS", &mut report)
+                .or(file.kill_switch.probe_interval_hours)
+                .unwrap_or(0);
+        let kill_switch_probe_url = env_var("KILL_SWITCH_PROBE_URL", &mut report)
+            .or(file.kill_switch.probe_url)
+            .unwrap_or_else(|| killswitch::DEFAULT_PROBE_URL.to_string());
+
+        let lookup_proxy = env_var("IP_LOOKUP_PROXY", &mut report).or(file.lookup.proxy);
+
+        // Parse provider chain, default to Gluetun (if configured) then public services
+        // Gluetun's answer would not go through the proxy, so it is left out in proxy mode
+        let ip_lookup_providers: Vec<String> = match env_var("IP_LOOKUP_PROVIDERS", &mut report) {
+            Some(list) => list
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            None => file.lookup.providers,
+        };
+        let ip_lookup_providers = if ip_lookup_providers.is_empty() {
+            default_providers(gluetun_url.is_some() && lookup_proxy.is_none())
+        } else {
+            ip_lookup_providers
+        };
+
+        let lookup_mode = env_var("IP_LOOKUP_MODE", &mut report)
+            .or(file.lookup.mode)
+            .and_then(|mode| {
+                mode.parse::<LookupMode>()
+                    .map_err(|e| report.errors.push(format!("IP_LOOKUP_MODE: {}", e)))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        let dual_stack = env_bool("IP_LOOKUP_DUAL_STACK", &mut report)
+            .or(file.lookup.dual_stack)
+            .unwrap_or(false);
+        let ipv6_lookup_providers: Vec<String> = match env_var("IPV6_LOOKUP_PROVIDERS", &mut report)
+        {
+            Some(list) => list
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            None => file.lookup.ipv6_providers,
+        };
+        let ipv6_lookup_providers = if ipv6_lookup_providers.is_empty() {
+            ip_lookup::DEFAULT_IPV6_PROVIDERS
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        } else {
+            ipv6_lookup_providers
+        };
+
+        let provider_timeouts = match env_var("IP_LOOKUP_TIMEOUTS", &mut report) {
+            Some(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .filter_map(|entry| {
+                    let parsed = entry
+                        .split_once('=')
+                        .and_then(|(name, secs)| Some((name.trim(), secs.trim().parse().ok()?)));
+                    if parsed.is_none() {
+                        report.errors.push(format!(
+                            "IP_LOOKUP_TIMEOUTS: '{}' is not provider=seconds (e.g. gluetun=3)",
+                            entry
+                        ));
+                    }
+                    parsed
+                })
+                .map(|(name, secs)| (name.to_lowercase(), secs))
+                .collect(),
+            None => file
+                .lookup
+                .timeouts
+                .into_iter()
+                .map(|(name, secs)| (name.to_lowercase(), secs))
+                .collect(),
+        };
 
         Self {
             allowed_asns,
-            ntfy_url,
+            denied_asns,
+            asn_labels,
+            geofence,
+            ntfy_urls,
             gluetun_url,
             gluetun_api_key,
             notification_interval_hours,
+            notify_on_reload,
             check_interval_minutes,
+            ip_lookup_providers,
+            provider_timeouts,
+            lookup_mode,
+            dual_stack,
+            ipv6_lookup_providers,
+            lookup_proxy,
+            lookup_cache_ttl_seconds,
+            asn_database,
+            bind_addr,
+            port,
+            unix_socket,
+            admin_token,
+            admin_state_file,
+            learn_window_hours,
+            check_rules,
+            allowed_cidrs,
+            dns_leak_check,
+            dns_whoami,
+            dns_probe_names,
+            dns_server,
+            dns_allowed,
+            home_ips,
+            home_lookup_url,
+            kill_switch_probe_interval_hours,
+            kill_switch_probe_url,
+            parse_report: report,
+        }
+    }
+
+    /// Validate the configuration, returning all errors and warnings found
+    pub fn validate(&self) -> ConfigReport {
+        self.validate_with_database(None).0
+    }
+
+    /// Validate the configuration with an already loaded `ASN_DATABASE`
+    ///
+    /// `ASN_DATABASE` is only loaded when `database` is `None`. The database used is
+    /// returned, so startup loads it once for validation and the lookups.
+    pub fn validate_with_database(
+        &self,
+        database: Option<Arc<ip_lookup::AsnDatabase>>,
+    ) -> (ConfigReport, Option<Arc<ip_lookup::AsnDatabase>>) {
+        let mut report = self.parse_report.clone();
+
+        let policy = match Policy::from_config(self) {
+            Ok(policy) => policy,
+            Err(e) => {
+                report.errors.push(format!("CHECK_RULES: {}", e));
+                Policy::default()
+            }
+        };
+
+        if self.allowed_asns.is_empty() && policy.has_rule(RuleKind::AsnAllow) {
+            report.errors.push(
+                "VPN_ALLOWED_ASNS is empty, /check would always fail (e.g. VPN_ALLOWED_ASNS=AS12345, or set VPN_ALLOWED_CIDRS instead)"
+                    .to_string(),
+            );
+        }
+
+        let mut asns: Vec<&String> = self.allowed_asns.iter().collect();
+        asns.sort();
+        for asn in asns {
+            if let Err(e) = validate_asn(asn) {
+                report.errors.push(format!("VPN_ALLOWED_ASNS: {}", e));
+            }
+        }
+
+        let mut denied: Vec<&String> = self.denied_asns.iter().collect();
+        denied.sort();
+        for asn in denied {
+            if let Err(e) = validate_asn(asn) {
+                report.errors.push(format!("VPN_DENIED_ASNS: {}", e));
+            }
+            if self.allowed_asns.contains(asn) {
+                report.errors.push(format!(
+                    "{} is in both VPN_ALLOWED_ASNS and VPN_DENIED_ASNS",
+                    asn
+                ));
+            }
+        }
+
+        let mut resolver_asns: Vec<&String> = self.dns_allowed.asns.iter().collect();
+        resolver_asns.sort();
+        for asn in resolver_asns {
+            if let Err(e) = validate_asn(asn) {
+                report
+                    .errors
+                    .push(format!("DNS_ALLOWED_RESOLVER_ASNS: {}", e));
+            }
+        }
+
+        for (rule, var) in [
+            (&self.geofence.countries, "COUNTRIES"),
+            (&self.geofence.regions, "REGIONS"),
+            (&self.geofence.cities, "CITIES"),
+        ] {
+            let mut both: Vec<&String> = rule.allowed.intersection(&rule.denied).collect();
+            both.sort();
+            for name in both {
+                report.errors.push(format!(
+                    "'{}' is in both VPN_ALLOWED_{} and VPN_DENIED_{}",
+                    name, var, var
+                ));
+            }
+        }
+
+        for name in self.geofence.countries.unknown_countries() {
+            report.warnings.push(format!(
+                "VPN_ALLOWED_COUNTRIES/VPN_DENIED_COUNTRIES: '{}' is not an ISO 3166 country name or code, it only matches providers reporting it as written",
+                name
+            ));
+        }
+
+        if policy.has_rule(RuleKind::AsnDeny) && self.denied_asns.is_empty() {
+            report
+                .warnings
+                .push("CHECK_RULES includes asn_deny but VPN_DENIED_ASNS is empty".to_string());
+        }
+        if policy.has_rule(RuleKind::Location) && self.geofence.is_empty() {
+            report.warnings.push(
+                "CHECK_RULES includes location but no country, region or city rules are set"
+                    .to_string(),
+            );
         }
+        if policy.has_rule(RuleKind::IpCidr) && self.allowed_cidrs.is_empty() {
+            report.errors.push(
+                "CHECK_RULES includes ip_cidr but VPN_ALLOWED_CIDRS is empty, /check would always fail"
+                    .to_string(),
+            );
+        }
+        if policy.has_rule(RuleKind::GluetunSource)
+            && !self
+                .ip_lookup_providers
+                .iter()
+                .any(|spec| spec.trim().to_lowercase().starts_with("gluetun"))
+        {
+            report.errors.push(
+                "CHECK_RULES includes gluetun_source but gluetun is not in IP_LOOKUP_PROVIDERS, /check would always fail"
+                    .to_string(),
+            );
+        }
+
+        if policy.has_rule(RuleKind::Consensus) && self.lookup_mode != LookupMode::Consensus {
+            report.errors.push(
+                "CHECK_RULES includes consensus but IP_LOOKUP_MODE is not consensus, /check would always fail"
+                    .to_string(),
+            );
+        }
+        if self.lookup_mode == LookupMode::Consensus && self.ip_lookup_providers.len() < 2 {
+            report.warnings.push(
+                "IP_LOOKUP_MODE is consensus but only one provider is configured, there is nothing to compare"
+                    .to_string(),
+            );
+        }
+        if policy.has_rule(RuleKind::DnsLeak) && !self.dns_leak_check {
+            report.errors.push(
+                "CHECK_RULES includes dns_leak but DNS_LEAK_CHECK is not enabled, /check would always fail"
+                    .to_string(),
+            );
+        }
+        if self.dns_leak_check
+            && self.dns_allowed.resolvers.is_empty()
+            && self.asn_database.is_none()
+        {
+            report.warnings.push(
+                "DNS_LEAK_CHECK needs DNS_ALLOWED_RESOLVERS or ASN_DATABASE to tell allowed resolvers apart, every resolver will be reported as not allowed"
+                    .to_string(),
+            );
+        }
+        if policy.has_rule(RuleKind::Ipv6Leak) && !self.dual_stack {
+            report.errors.push(
+                "CHECK_RULES includes ipv6_leak but IP_LOOKUP_DUAL_STACK is not enabled, /check would always fail"
+                    .to_string(),
+            );
+        }
+        if policy.has_rule(RuleKind::KillSwitch) && !self.kill_switch_enabled() {
+            report.errors.push(
+                "CHECK_RULES includes kill_switch but neither HOME_IPS, HOME_LOOKUP_URL nor KILL_SWITCH_PROBE_INTERVAL_HOURS is set, /check would always fail"
+                    .to_string(),
+            );
+        }
+        if policy.has_rule(RuleKind::Tunnel) && self.gluetun_url.is_none() {
+            report.errors.push(
+                "CHECK_RULES includes tunnel but GLUETUN_API_URL is not set, /check would always fail"
+                    .to_string(),
+            );
+        }
+        if self.kill_switch_enabled() && self.gluetun_url.is_none() {
+            report.errors.push(
+                "kill switch verification needs GLUETUN_API_URL, the exit IP and the tunnel-stop probe go through Gluetun's control server"
+                    .to_string(),
+            );
+        }
+        if let Some(ref url) = self.home_lookup_url {
+            if let Err(e) = validate_url(url) {
+                report.errors.push(format!("HOME_LOOKUP_URL: {}", e));
+            }
+        }
+        if self.kill_switch_probe_interval_hours > 0 {
+            if let Err(e) = validate_url(&self.kill_switch_probe_url) {
+                report.errors.push(format!("KILL_SWITCH_PROBE_URL: {}", e));
+            }
+        }
+        if let Some(ref proxy) = self.lookup_proxy {
+            if let Err(e) = ip_lookup::parse_proxy_url(proxy) {
+                report.errors.push(format!("IP_LOOKUP_PROXY: {}", e));
+            }
+            if self.dual_stack {
+                report.errors.push(
+                    "IP_LOOKUP_PROXY and IP_LOOKUP_DUAL_STACK cannot be combined, the proxy decides the address family"
+                        .to_string(),
+                );
+            }
+            // `offline` takes its IP from Gluetun unless given a URL
+            let first = self
+                .ip_lookup_providers
+                .first()
+                .map(|spec| spec.trim().to_lowercase());
+            if first.is_some_and(|spec| spec == "gluetun" || spec == "offline") {
+                report.warnings.push(
+                    "IP_LOOKUP_PROXY is set but the first provider asks the Gluetun API directly, the exit seen through the proxy is only checked when it fails"
+                        .to_string(),
+                );
+            }
+        }
+        if self.lookup_mode == LookupMode::Race && self.provider_timeouts.is_empty() {
+            report.warnings.push(
+                "IP_LOOKUP_MODE is race but no lookup timeouts are set, a hung provider still delays lookups by 30s (e.g. IP_LOOKUP_TIMEOUTS=default=5)"
+                    .to_string(),
+            );
+        }
+
+        if let Some(ref url) = self.gluetun_url {
+            if let Err(e) = validate_url(url) {
+                report.errors.push(format!("GLUETUN_API_URL: {}", e));
+            }
+        } else if self.gluetun_api_key.is_some() {
+            report.warnings.push(
+                "GLUETUN_API_KEY is set but GLUETUN_API_URL is not, the key is unused".to_string(),
+            );
+        }
+
+        if self.learn_window_hours > MAX_LEARN_WINDOW_HOURS {
+            report.errors.push(format!(
+                "ASN_LEARN_WINDOW_HOURS: {} is longer than one year ({} hours)",
+                self.learn_window_hours, MAX_LEARN_WINDOW_HOURS
+            ));
+        }
+
+        // Only a stale socket is replaced on startup, never a mistyped path's file
+        if let Some(ref path) = self.unix_socket {
+            if fs::symlink_metadata(path).is_ok_and(|metadata| !metadata.file_type().is_socket()) {
+                report.errors.push(format!(
+                    "MONITOR_UNIX_SOCKET: {} exists and is not a socket",
+                    path.display()
+                ));
+            }
+        }
+
+        for url in &self.ntfy_urls {
+            if let Err(e) = validate_url(url) {
+                report
+                    .errors
+                    .push(format!("NTFY_URL: {}", e.replace(url, &redact_url(url))));
+            }
+        }
+
+        if policy.has_rule(RuleKind::AsnCrosscheck) && self.asn_database.is_none() {
+            report.errors.push(
+                "CHECK_RULES includes asn_crosscheck but ASN_DATABASE is not set".to_string(),
+            );
+        }
+
+        let asn_database = database.or_else(|| {
+            self.asn_database.as_deref().and_then(|path| {
+                ip_lookup::AsnDatabase::load(path)
+                    .map(Arc::new)
+                    .map_err(|e| report.errors.push(format!("ASN_DATABASE: {}", e)))
+                    .ok()
+            })
+        });
+
+        // Building the chain checks provider names and Gluetun availability
+        if let Err(e) = ip_lookup::build_providers(
+            &self.ip_lookup_providers,
+            self.gluetun_url.as_deref(),
+            self.gluetun_api_key.as_deref(),
+            &self.provider_timeouts,
+            asn_database.as_ref(),
+        ) {
+            report.errors.push(format!("IP_LOOKUP_PROVIDERS: {}", e));
+        }
+
+        if self.dual_stack {
+            if let Err(e) = ip_lookup::build_ipv6_providers(
+                &self.ipv6_lookup_providers,
+                &self.provider_timeouts,
+                asn_database.as_ref(),
+            ) {
+                report.errors.push(format!("IPV6_LOOKUP_PROVIDERS: {}", e));
+            }
+        }
+
+        for (var, specs) in [
+            ("IP_LOOKUP_PROVIDERS", &self.ip_lookup_providers),
+            ("IPV6_LOOKUP_PROVIDERS", &self.ipv6_lookup_providers),
+        ] {
+            for spec in specs {
+                if let Some((_, url)) = spec.split_once('=') {
+                    if let Err(e) = validate_url(url.trim()) {
+                        report.errors.push(format!("{}: '{}': {}", var, spec, e));
+                    }
+                }
+            }
+        }
+
+        let mut timeouts: Vec<(&String, &u64)> = self.provider_timeouts.iter().collect();
+        timeouts.sort();
+        let ipv6_specs: &[String] = if self.dual_stack {
+            &self.ipv6_lookup_providers
+        } else {
+            &[]
+        };
+        for (name, secs) in timeouts {
+            let in_chain = name == ip_lookup::DEFAULT_TIMEOUT
+                || self
+                    .ip_lookup_providers
+                    .iter()
+                    .chain(ipv6_specs)
+                    .any(|spec| {
+                        spec.split('=')
+                            .next()
+                            .unwrap_or_default()
+                            .trim()
+                            .eq_ignore_ascii_case(name)
+                    });
+            if !in_chain {
+                report.warnings.push(format!(
+                    "lookup timeout set for '{}', which is not in the provider chain",
+                    name
+                ));
+            }
+            if *secs == 0 {
+                report.errors.push(format!(
+                    "lookup timeout for '{}' must be at least 1 second",
+                    name
+                ));
+            }
+        }
+
+        match self.admin_token {
+            Some(ref token) if token.len() < MIN_ADMIN_TOKEN_LEN => {
+                report.warnings.push(format!(
+                    "ADMIN_TOKEN is shorter than {} characters, use a long random value",
+                    MIN_ADMIN_TOKEN_LEN
+                ));
+            }
+            None if self.admin_state_file.is_some() => {
+                report.warnings.push(
+                    "ADMIN_STATE_FILE is set but ADMIN_TOKEN is not, the admin API is disabled"
+                        .to_string(),
+                );
+            }
+            _ => {}
+        }
+
+        (report, asn_database)
+    }
+
+    /// Whether Gluetun's exit is compared with the home IP or the tunnel-stop probe runs
+    pub fn kill_switch_enabled(&self) -> bool {
+        !self.home_ips.is_empty()
+            || self.home_lookup_url.is_some()
+            || self.kill_switch_probe_interval_hours > 0
+    }
+
+    /// TCP socket address the HTTP server listens on
+    pub fn listen_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_addr, self.port)
+    }
+
+    /// Effective configuration with secrets redacted, for logging and `/config`
+    pub fn redacted(&self) -> serde_json::Value {
+        let labeled = |asns: &HashSet<String>| {
+            let mut entries: Vec<serde_json::Value> = asns
+                .iter()
+                .map(|asn| match self.asn_labels.get(asn) {
+                    Some(label) => serde_json::json!({ "asn": asn, "label": label }),
+                    None => serde_json::json!(asn),
+                })
+                .collect();
+            entries.sort_by_key(|v| v.to_string());
+            entries
+        };
+
+        serde_json::json!({
+            "vpn": {
+                "allowed_asns": labeled(&self.allowed_asns),
+                "denied_asns": labeled(&self.denied_asns),
+                "allowed_cidrs": self.allowed_cidrs.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                "check_interval_minutes": self.check_interval_minutes,
+            },
+            "geofence": {
+                "allowed_countries": sorted(&self.geofence.countries.allowed),
+                "denied_countries": sorted(&self.geofence.countries.denied),
+                "allowed_regions": sorted(&self.geofence.regions.allowed),
+                "denied_regions": sorted(&self.geofence.regions.denied),
+                "allowed_cities": sorted(&self.geofence.cities.allowed),
+                "denied_cities": sorted(&self.geofence.cities.denied),
+            },
+            "gluetun": {
+                "api_url": self.gluetun_url,
+                "api_key": self.gluetun_api_key.as_ref().map(|_| REDACTED),
+            },
+            "notifications": {
+                "interval_hours": self.notification_interval_hours,
+                "on_reload": self.notify_on_reload,
+            },
+            "notifiers": self
+                .ntfy_urls
+                .iter()
+                .map(|url| serde_json::json!({ "url": redact_url(url) }))
+                .collect::<Vec<_>>(),
+            "lookup": {
+                "providers": self.ip_lookup_providers,
+                "mode": self.lookup_mode.name(),
+                "dual_stack": self.dual_stack,
+                "ipv6_providers": self.ipv6_lookup_providers,
+                "proxy": self.lookup_proxy.as_deref().map(redact_url),
+                "cache_ttl_seconds": self.lookup_cache_ttl_seconds,
+                "timeouts": self.provider_timeouts,
+                "asn_database": self.asn_database,
+            },
+            "server": {
+                "bind_addr": self.bind_addr,
+                "port": self.port,
+                "unix_socket": self.unix_socket,
+            },
+            "learn": {
+                "window_hours": self.learn_window_hours,
+            },
+            "admin": {
+                "token": self.admin_token.as_ref().map(|_| REDACTED),
+                "state_file": self.admin_state_file,
+            },
+            "check": {
+                "rules": self.check_rules,
+            },
+            "dns": {
+                "leak_check": self.dns_leak_check,
+                "whoami": self.dns_whoami,
+                "names": self.dns_probe_names,
+                "server": self.dns_server,
+                "allowed_resolvers": self.dns_allowed.resolvers.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                "allowed_resolver_asns": sorted(&self.dns_allowed.asns),
+            },
+            "kill_switch": {
+                // The home IP is exactly what the VPN hides
+                "home_ips": self.home_ips.iter().map(|_| REDACTED).collect::<Vec<_>>(),
+                "home_lookup_url": self.home_lookup_url.as_ref().map(|_| REDACTED),
+                "probe_interval_hours": self.kill_switch_probe_interval_hours,
+                "probe_url": self.kill_switch_probe_url,
+            },
+        })
+    }
+}
+
+/// Shortest admin token accepted without a warning
+const MIN_ADMIN_TOKEN_LEN: usize = 16;
+
+/// Longest `ASN_LEARN_WINDOW_HOURS` accepted, one year
+const MAX_LEARN_WINDOW_HOURS: u64 = 24 * 365;
+
+/// Placeholder for redacted secrets
+const REDACTED: &str = "***";
+
+/// Hide credentials and the path (e.g. ntfy topic) of a URL, keeping scheme and host
+pub fn redact_url(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => return REDACTED.to_string(),
+    };
+
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+
+    if authority.len() == rest.len() {
+        format!("{}://{}", scheme, host)
+    } else {
+        format!("{}://{}/{}", scheme, host, REDACTED)
+    }
+}
+
+/// Read a non-empty environment variable
+///
+/// Follows the `*_FILE` convention for secrets: if `NAME_FILE` is set instead of `NAME`,
+/// the value is read from that file (e.g. a Docker or Kubernetes secret).
+/// Setting both, or an unreadable file, is recorded as an error in `report`.
+fn env_var(name: &str, report: &mut ConfigReport) -> Option<String> {
+    let direct = read_env(name);
+    let file_var = format!("{}_FILE", name);
+    let Some(path) = read_env(&file_var) else {
+        return direct;
+    };
+
+    if direct.is_some() {
+        report.errors.push(format!(
+            "both {} and {} are set, use only one",
+            name, file_var
+        ));
+        return direct;
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => Some(contents.trim().to_string()).filter(|s| !s.is_empty()),
+        Err(e) => {
+            report
+                .errors
+                .push(format!("{}: failed to read {}: {}", file_var, path, e));
+            None
+        }
+    }
+}
+
+/// Read a trimmed, non-empty environment variable as-is
+fn read_env(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Read and parse an environment variable, `None` if unset or unparsable
+///
+/// Unparsable values are recorded as errors in `report`.
+fn env_parse<T: std::str::FromStr>(name: &str, report: &mut ConfigReport) -> Option<T> {
+    let value = env_var(name, report)?;
+    match value.parse::<T>() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            report
+                .errors
+                .push(format!("{}: '{}' is not a valid number", name, value));
+            None
+        }
+    }
+}
+
+/// Read a boolean environment variable (`true`/`false`, `1`/`0`, `yes`/`no`)
+///
+/// Other values are recorded as errors in `report`.
+fn env_bool(name: &str, report: &mut ConfigReport) -> Option<bool> {
+    let value = env_var(name, report)?;
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => {
+            report
+                .errors
+                .push(format!("{}: '{}' is not a valid boolean", name, value));
+            None
+        }
+    }
+}
+
+/// Set contents in a stable order for display
+fn sorted(set: &HashSet<String>) -> Vec<&String> {
+    let mut items: Vec<&String> = set.iter().collect();
+    items.sort();
+    items
+}
+
+/// Split a comma-separated list, dropping empty entries
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse a comma-separated ASN list from an environment variable
+fn parse_asn_list(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Collect ASNs from config file entries, recording their labels
+fn parse_asn_entries(
+    entries: Vec<AsnEntry>,
+    labels: &mut HashMap<String, String>,
+) -> HashSet<String> {
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let (asn, label) = match entry {
+                AsnEntry::Plain(asn) => (asn, None),
+                AsnEntry::Labeled { asn, label } => (asn, label),
+            };
+            let asn = asn.trim().to_uppercase();
+            if asn.is_empty() {
+                return None;
+            }
+            if let Some(label) = label {
+                labels.insert(asn.clone(), label);
+            }
+            Some(asn)
+        })
+        .collect()
+}
+
+/// Check that an ASN looks like `AS` followed by digits
+pub fn validate_asn(asn: &str) -> Result<(), String> {
+    match asn.strip_prefix("AS") {
+        Some(digits) if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => Ok(()),
+        _ if !asn.is_empty() && asn.chars().all(|c| c.is_ascii_digit()) => Err(format!(
+            "'{}' is missing the AS prefix (did you mean AS{}?)",
+            asn, asn
+        )),
+        _ => Err(format!(
+            "'{}' is not a valid ASN (expected AS followed by digits)",
+            asn
+        )),
+    }
+}
+
+/// Check that a URL is absolute http(s) with a host
+fn validate_url(url: &str) -> Result<(), String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("'{}' is not a valid URL ({})", url, e))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("'{}' must use http or https", url));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(format!("'{}' has no host", url));
+    }
+
+    Ok(())
+}
+
+/// Default provider chain when `IP_LOOKUP_PROVIDERS` is not set
+fn default_providers(gluetun_configured: bool) -> Vec<String> {
+    let mut providers = Vec::with_capacity(3);
+    if gluetun_configured {
+        providers.push("gluetun".to_string());
     }
+    providers.push("ifconfig".to_string());
+    providers.push("ipapi".to_string());
+    providers
 }
@@ -0,0 +1,168 @@
+// ASN Discovery Module
+//!
+//! Implements the `discover-asns` command: reconnects the VPN through the
+//! Gluetun control server (`PUT /v1/vpn/status`) several times, waits for a
+//! new public IP after each reconnect and collects the ASNs seen.
+//!
+//! Replaces `scripts/discover-asns.sh`, which had to restart the whole
+//! compose stack for every sample.
+
+use crate::{
+    ip_lookup::{fetch_gluetun_ip, fetch_vpn_status, set_vpn_status},
+    models::{LookupResult, TunnelState},
+};
+use reqwest::Client;
+use std::collections::BTreeMap;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Settings for a discovery run
+#[derive(Debug, Clone)]
+pub struct DiscoverOptions {
+    /// Number of reconnects
+    pub iterations: u32,
+    /// How long to wait for a new public IP after each reconnect
+    pub wait_timeout: Duration,
+    /// Delay between public IP checks while waiting
+    pub poll_interval: Duration,
+}
+
+/// Outcome of one reconnect
+#[derive(Debug, Clone)]
+pub struct Reconnect {
+    pub lookup: LookupResult,
+    /// False if the server handed out the same exit IP again
+    pub ip_changed: bool,
+}
+
+/// Stop and restart the VPN, then wait for Gluetun to report a new public IP
+///
+/// An unchanged IP is accepted as soon as Gluetun reports the tunnel running
+/// again. Fails if the tunnel cannot be toggled or no lookup succeeds before
+/// the timeout.
+pub async fn reconnect(
+    client: &Client,
+    gluetun_url: &str,
+    api_key: Option<&str>,
+    options: &DiscoverOptions,
+) -> Result<Reconnect, String> {
+    let previous_ip = fetch_gluetun_ip(client, gluetun_url, api_key)
+        .await
+        .ok()
+        .and_then(|info| info.ip);
+
+    set_vpn_status(client, gluetun_url, api_key, "stopped")
+        .await
+        .map_err(|e| format!("failed to stop the VPN: {}", e))?;
+    set_vpn_status(client, gluetun_url, api_key, "running")
+        .await
+        .map_err(|e| format!("failed to start the VPN: {}", e))?;
+
+    let deadline = Instant::now() + options.wait_timeout;
+    let mut last = None;
+    let mut last_error = None;
+
+    loop {
+        match fetch_gluetun_ip(client, gluetun_url, api_key).await {
+            Ok(info) if info.ip.is_some() && info.ip != previous_ip => {
+                return Ok(Reconnect {
+                    lookup: info,
+                    ip_changed: true,
+                });
+            }
+            // The server may hand out the same exit IP again, its ASN still counts
+            Ok(info) if info.ip.is_some() && tunnel_running(client, gluetun_url, api_key).await => {
+                return Ok(Reconnect {
+                    lookup: info,
+                    ip_changed: false,
+                });
+            }
+            Ok(info) => last = Some(info),
+            Err(e) => last_error = Some(e),
+        }
+
+        if Instant::now() + options.poll_interval > deadline {
+            break;
+        }
+        sleep(options.poll_interval).await;
+    }
+
+    // Gluetun did not report the tunnel running, use the last known IP
+    match (last, last_error) {
+        (Some(info), _) => Ok(Reconnect {
+            lookup: info,
+            ip_changed: false,
+        }),
+        (None, Some(e)) => Err(format!("no public IP after reconnecting: {}", e)),
+        (None, None) => Err("no public IP after reconnecting".to_string()),
+    }
+}
+
+async fn tunnel_running(client: &Client, gluetun_url: &str, api_key: Option<&str>) -> bool {
+    matches!(
+        fetch_vpn_status(client, gluetun_url, api_key).await,
+        Ok(TunnelState::Running)
+    )
+}
+
+/// Run every reconnect, printing progress, and return the ASNs seen with their organization
+pub async fn run(
+    client: &Client,
+    gluetun_url: &str,
+    api_key: Option<&str>,
+    options: &DiscoverOptions,
+) -> BTreeMap<String, Option<String>> {
+    let mut asns = BTreeMap::new();
+
+    println!("🔍 Discovering VPN Exit ASNs");
+    println!("--------------------------------");
+    println!("Gluetun API: {}", gluetun_url);
+    println!("Iterations: {}", options.iterations);
+
+    for i in 1..=options.iterations {
+        println!();
+        println!("▶ Iteration {}/{}", i, options.iterations);
+
+        match reconnect(client, gluetun_url, api_key, options).await {
+            Ok(result) => {
+                let info = result.lookup;
+                if !result.ip_changed {
+                    println!("  … public IP did not change");
+                }
+                println!("  ✔ IP:  {}", info.ip.as_deref().unwrap_or("Unknown"));
+                println!("  ✔ ASN: {}", info.asn.as_deref().unwrap_or("Unknown"));
+                println!("  ✔ Org: {}", info.org.as_deref().unwrap_or("Unknown"));
+                if let Some(asn) = info.asn {
+                    asns.insert(asn, info.org);
+                }
+            }
+            Err(e) => println!("  ✖ {}", e),
+        }
+
+        println!("  🔁 Unique ASNs so far: {}", join_asns(&asns));
+    }
+
+    println!();
+    println!("✅ Discovery complete");
+    println!("--------------------------------");
+    println!("Distinct ASNs found:");
+    for (asn, org) in &asns {
+        match org {
+            Some(org) => println!("  - {} ({})", asn, org),
+            None => println!("  - {}", asn),
+        }
+    }
+    println!();
+    println!("Add this to your .env file:");
+    println!("{}", allowed_asns_line(&asns));
+
+    asns
+}
+
+/// `VPN_ALLOWED_ASNS=...` line for the discovered ASNs
+pub fn allowed_asns_line(asns: &BTreeMap<String, Option<String>>) -> String {
+    format!("VPN_ALLOWED_ASNS={}", join_asns(asns))
+}
+
+fn join_asns(asns: &BTreeMap<String, Option<String>>) -> String {
+    asns.keys().cloned().collect::<Vec<_>>().join(",")
+}
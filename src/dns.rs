@@ -0,0 +1,272 @@
+// DNS Module
+//!
+//! DNS leak probe: finds out which resolvers answer the monitor's DNS queries.
+//!
+//! A "whoami" name (default `whoami.akamai.net`) is answered by its authoritative
+//! server with the address of the resolver that asked, so resolving it through the
+//! system resolver reveals where DNS queries actually leave. Behind Gluetun that
+//! should be the VPN provider's resolver, not the home ISP's.
+//!
+//! ## Probe
+//! - Resolves the whoami name and `DNS_PROBE_NAMES` through the system resolver,
+//!   or directly over UDP via `DNS_SERVER` (e.g. Gluetun's DNS or a local stand-in)
+//! - Resolver ASNs come from `ASN_DATABASE` when it is loaded
+//!
+//! The `dns_leak` rule checks the resolvers against `DNS_ALLOWED_RESOLVERS`,
+//! `DNS_ALLOWED_RESOLVER_ASNS` (or `VPN_ALLOWED_ASNS` when neither is set) and
+//! `VPN_DENIED_ASNS`.
+
+use crate::{
+    ip_lookup::AsnDatabase,
+    models::{DnsName, DnsReport, DnsResolver},
+};
+use ipnet::IpNet;
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+
+/// Default whoami name, answered with the querying resolver's address
+pub const DEFAULT_WHOAMI: &str = "whoami.akamai.net";
+
+/// How long a single resolution may take
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolvers `/check` accepts
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DnsAllowList {
+    pub resolvers: Vec<IpNet>,
+    pub asns: HashSet<String>,
+}
+
+impl DnsAllowList {
+    /// True when neither resolvers nor ASNs are set
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty() && self.asns.is_empty()
+    }
+}
+
+/// Resolves the whoami and probe names
+#[derive(Debug)]
+pub struct DnsProbe {
+    whoami: String,
+    names: Vec<String>,
+    /// Query this server over UDP instead of the system resolver
+    server: Option<SocketAddr>,
+    asn_database: Option<Arc<AsnDatabase>>,
+}
+
+impl DnsProbe {
+    pub fn new(
+        whoami: &str,
+        names: Vec<String>,
+        server: Option<SocketAddr>,
+        asn_database: Option<Arc<AsnDatabase>>,
+    ) -> Self {
+        Self {
+            whoami: whoami.to_string(),
+            names,
+            server,
+            asn_database,
+        }
+    }
+
+    /// Run the probe
+    pub async fn run(&self) -> DnsReport {
+        let (resolvers, error) = match self.resolve(&self.whoami).await {
+            Ok(addrs) => {
+                let resolvers = addrs
+                    .into_iter()
+                    .map(|ip| {
+                        let record = self
+                            .asn_database
+                            .as_ref()
+                            .and_then(|database| database.resolve(ip));
+                        DnsResolver {
+                            ip: ip.to_string(),
+                            asn: record.as_ref().map(|r| r.asn.clone()),
+                            org: record.and_then(|r| r.org),
+                        }
+                    })
+                    .collect();
+                (resolvers, None)
+            }
+            Err(e) => {
+                warn!("DNS probe failed to resolve {}: {}", self.whoami, e);
+                (Vec::new(), Some(format!("{}: {}", self.whoami, e)))
+            }
+        };
+
+        let mut names = Vec::with_capacity(self.names.len());
+        for name in &self.names {
+            let result = self.resolve(name).await;
+            if let Err(ref e) = result {
+                debug!("DNS probe failed to resolve {}: {}", name, e);
+            }
+            names.push(DnsName {
+                name: name.clone(),
+                addresses: result
+                    .as_ref()
+                    .map(|addrs| addrs.iter().map(ToString::to_string).collect())
+                    .unwrap_or_default(),
+                error: result.err(),
+            });
+        }
+
+        DnsReport {
+            server: self.server.map(|server| server.to_string()),
+            resolvers,
+            names,
+            error,
+        }
+    }
+
+    async fn resolve(&self, name: &str) -> Result<Vec<IpAddr>, String> {
+        let lookup = async {
+            match self.server {
+                Some(server) => query_server(server, name).await,
+                None => tokio::net::lookup_host((name, 0))
+                    .await
+                    .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+                    .map_err(|e| e.to_string()),
+            }
+        };
+        let mut addrs = tokio::time::timeout(RESOLVE_TIMEOUT, lookup)
+            .await
+            .map_err(|_| "timed out".to_string())??;
+        addrs.sort();
+        addrs.dedup();
+        if addrs.is_empty() {
+            return Err("no addresses".to_string());
+        }
+        Ok(addrs)
+    }
+}
+
+/// Parse `DNS_SERVER`, the port defaults to 53
+pub fn parse_server(value: &str) -> Result<SocketAddr, String> {
+    let value = value.trim();
+    value
+        .parse::<SocketAddr>()
+        .or_else(|_| {
+            value
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, 53))
+        })
+        .map_err(|_| format!("'{}' is not an IP address or IP:port", value))
+}
+
+/// Query `server` directly for the A and AAAA records of `name`
+async fn query_server(server: SocketAddr, name: &str) -> Result<Vec<IpAddr>, String> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await.map_err(|e| e.to_string())?;
+    socket.connect(server).await.map_err(|e| e.to_string())?;
+
+    let mut addrs = Vec::new();
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        // Unpredictable IDs, so an off-path attacker cannot forge the answer
+        let id = getrandom::u32().map_err(|e| e.to_string())? as u16;
+        socket
+            .send(&encode_query(id, name, qtype)?)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut buf = [0u8; 1232];
+        let len = socket.recv(&mut buf).await.map_err(|e| e.to_string())?;
+        addrs.extend(decode_answers(id, &buf[..len])?);
+    }
+    Ok(addrs)
+}
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Build a recursive query for one name and record type
+fn encode_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    let mut packet = Vec::with_capacity(18 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("'{}' is not a valid DNS name", name));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes()); // IN
+    Ok(packet)
+}
+
+/// Read the A and AAAA records of a response, skipping anything else
+fn decode_answers(id: u16, packet: &[u8]) -> Result<Vec<IpAddr>, String> {
+    let invalid = || "invalid DNS response".to_string();
+    let u16_at = |pos: usize| {
+        packet
+            .get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(invalid)
+    };
+
+    if u16_at(0)? != id {
+        return Err("DNS response ID mismatch".to_string());
+    }
+    let flags = u16_at(2)?;
+    // A truncated answer may be missing the very resolver that leaks
+    if flags & 0x0200 != 0 {
+        return Err("DNS response truncated".to_string());
+    }
+    let rcode = flags & 0x000f;
+    match rcode {
+        0 => {}
+        3 => return Err("NXDOMAIN".to_string()),
+        other => return Err(format!("DNS error code {}", other)),
+    }
+    let questions = u16_at(4)?;
+    let answers = u16_at(6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(packet, pos).ok_or_else(invalid)? + 4;
+    }
+
+    let mut addrs = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(packet, pos).ok_or_else(invalid)?;
+        let rtype = u16_at(pos)?;
+        let rdlength = u16_at(pos + 8)? as usize;
+        let rdata = packet
+            .get(pos + 10..pos + 10 + rdlength)
+            .ok_or_else(invalid)?;
+        match (rtype, rdata.len()) {
+            (TYPE_A, 4) => addrs.push(IpAddr::from(<[u8; 4]>::try_from(rdata).unwrap())),
+            (TYPE_AAAA, 16) => addrs.push(IpAddr::from(<[u8; 16]>::try_from(rdata).unwrap())),
+            _ => {}
+        }
+        pos += 10 + rdlength;
+    }
+    Ok(addrs)
+}
+
+/// Position after a possibly compressed name
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // Compression pointer, the name ends here
+            l if l & 0xc0 == 0xc0 => return Some(pos + 2),
+            l => pos += 1 + l as usize,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// Geofence Module
+//!
+//! Country, region and city rules for the VPN exit location, evaluated by
+//! `/check` alongside the ASN allowlist.
+//!
+//! Each level has an optional allow-list and deny-list. Names are compared
+//! case-insensitively against what the lookup provider reports. Countries are
+//! compared as ISO 3166-1 alpha-2 codes, so `Netherlands` (Gluetun) and `NL`
+//! (ipinfo.io) match either form in the lists. When an allow-list is set and the
+//! provider does not report that level, the check fails closed.
+
+use crate::{country::country_code, models::LookupResult};
+use std::collections::HashSet;
+
+/// Allowed and denied names for one location level
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LocationRule {
+    /// Lowercased names (ISO codes for countries), empty means any
+    pub allowed: HashSet<String>,
+    /// Lowercased names (ISO codes for countries)
+    pub denied: HashSet<String>,
+    /// Compare ISO 3166-1 alpha-2 codes instead of names
+    iso_codes: bool,
+}
+
+/// Location policy for the VPN exit
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Geofence {
+    pub countries: LocationRule,
+    pub regions: LocationRule,
+    pub cities: LocationRule,
+}
+
+impl LocationRule {
+    pub fn new<A, D>(allowed: A, denied: D) -> Self
+    where
+        A: IntoIterator<Item = String>,
+        D: IntoIterator<Item = String>,
+    {
+        Self {
+            allowed: normalize(allowed, false),
+            denied: normalize(denied, false),
+            iso_codes: false,
+        }
+    }
+
+    /// Country rule, names and codes are stored as uppercase ISO codes
+    ///
+    /// Names without a known code are kept lowercased and compared as written.
+    pub fn countries<A, D>(allowed: A, denied: D) -> Self
+    where
+        A: IntoIterator<Item = String>,
+        D: IntoIterator<Item = String>,
+    {
+        Self {
+            allowed: normalize(allowed, true),
+            denied: normalize(denied, true),
+            iso_codes: true,
+        }
+    }
+
+    /// Entries that are neither a known country name nor a code, for country rules
+    pub fn unknown_countries(&self) -> Vec<&String> {
+        let mut unknown: Vec<&String> = self
+            .allowed
+            .iter()
+            .chain(&self.denied)
+            .filter(|name| self.iso_codes && country_code(name).is_none())
+            .collect();
+        unknown.sort();
+        unknown
+    }
+
+    /// True when neither list is set
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty() && self.denied.is_empty()
+    }
+
+    /// Check a reported value, returning the violated rule
+    ///
+    /// `level` names the field (e.g. `country`) and `var` the setting suffix
+    /// (e.g. `COUNTRIES`) used in the message.
+    fn check(&self, level: &str, var: &str, value: Option<&str>) -> Result<(), String> {
+        let Some(value) = value else {
+            if self.allowed.is_empty() {
+                return Ok(());
+            }
+            return Err(format!(
+                "{} unknown, required by VPN_ALLOWED_{}",
+                level, var
+            ));
+        };
+
+        let key = key(value, self.iso_codes);
+        if self.denied.contains(&key) {
+            return Err(format!("{} '{}' is in VPN_DENIED_{}", level, value, var));
+        }
+        if !self.allowed.is_empty() && !self.allowed.contains(&key) {
+            return Err(format!(
+                "{} '{}' is not in VPN_ALLOWED_{}",
+                level, value, var
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Geofence {
+    /// True when no location rules are configured
+    pub fn is_empty(&self) -> bool {
+        self.countries.is_empty() && self.regions.is_empty() && self.cities.is_empty()
+    }
+
+    /// Every rule violated by the lookup's location, empty if it is within the geofence
+    pub fn violations(&self, info: &LookupResult) -> Vec<String> {
+        [
+            self.countries
+                .check("country", "COUNTRIES", info.country.as_deref()),
+            self.regions
+                .check("region", "REGIONS", info.region.as_deref()),
+            self.cities.check("city", "CITIES", info.city.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect()
+    }
+}
+
+/// Normalize names with [`key`], dropping empty ones
+fn normalize<I: IntoIterator<Item = String>>(names: I, iso_codes: bool) -> HashSet<String> {
+    names
+        .into_iter()
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| key(&name, iso_codes))
+        .collect()
+}
+
+/// Country code if known and asked for, otherwise the trimmed lowercase name
+fn key(name: &str, iso_codes: bool) -> String {
+    match country_code(name).filter(|_| iso_codes) {
+        Some(code) => code.to_string(),
+        None => name.trim().to_lowercase(),
+    }
+}
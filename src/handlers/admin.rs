@@ -0,0 +1,243 @@
+//! Admin Handler
+//!
+//! Provides the `/admin/asns` endpoints for managing the ASN allowlist at runtime.
+//! Requests must carry `Authorization: Bearer <ADMIN_TOKEN>`; without a configured
+//! token the endpoints return 404.
+//! `/admin/asns/promote` adds ASNs recorded by learn mode.
+//! Home ISP ASNs (`VPN_DENIED_ASNS`) are never allowed: adding one is a conflict,
+//! and promoting every observed ASN skips them.
+//! Changes are kept on top of the configured allowlist and, if `ADMIN_STATE_FILE`
+//! is set, persisted so they survive restarts.
+
+use crate::{
+    allowlist::AsnDiff,
+    config::validate_asn,
+    models::{AdminAsnsResponse, AppState, AsnRequest, ErrorResponse, PromoteRequest},
+};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::{error, info, warn};
+
+/// Handler for GET /admin/asns
+pub async fn admin_list_handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(e) = authorize(&state, &headers) {
+        return e.into_response();
+    }
+
+    let persisted = state.admin_state_file.is_some();
+    (StatusCode::OK, Json(allowlist_response(&state, persisted))).into_response()
+}
+
+/// Handler for POST /admin/asns
+pub async fn admin_add_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<AsnRequest>,
+) -> Response {
+    let asn = match authorize(&state, &headers)
+        .and_then(|_| normalize_asn(&request.asn))
+        .and_then(|asn| reject_denied(&state, asn))
+    {
+        Ok(asn) => asn,
+        Err(e) => return e.into_response(),
+    };
+
+    let result = state
+        .allowed_asns
+        .add(&[asn], state.admin_state_file.as_deref())
+        .await;
+    respond(&state, result).into_response()
+}
+
+/// Handler for DELETE /admin/asns
+pub async fn admin_remove_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<AsnRequest>,
+) -> Response {
+    let asn = match authorize(&state, &headers).and_then(|_| normalize_asn(&request.asn)) {
+        Ok(asn) => asn,
+        Err(e) => return e.into_response(),
+    };
+
+    let result = state
+        .allowed_asns
+        .remove(&asn, state.admin_state_file.as_deref())
+        .await;
+    respond(&state, result).into_response()
+}
+
+/// Handler for POST /admin/asns/promote
+///
+/// Without a body, every observed ASN is promoted.
+pub async fn admin_promote_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Option<Json<PromoteRequest>>,
+) -> Response {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    if let Err(e) = authorize(&state, &headers) {
+        return e.into_response();
+    }
+    promote(&state, request).await.into_response()
+}
+
+/// Admin API failure, rendered as a JSON error body
+struct AdminError {
+    status: StatusCode,
+    message: String,
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let mut response = (
+            self.status,
+            Json(ErrorResponse {
+                error: self.message,
+            }),
+        )
+            .into_response();
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Respond to an applied and persisted allowlist change with the new allowlist
+fn respond(
+    state: &AppState,
+    result: Result<AsnDiff, String>,
+) -> Result<Json<AdminAsnsResponse>, AdminError> {
+    let persisted = state.admin_state_file.is_some();
+    match result {
+        Ok(diff) => {
+            if !diff.is_empty() {
+                info!("Allowlist changed via admin API: {}", diff);
+            }
+            Ok(Json(allowlist_response(state, persisted)))
+        }
+        Err(e) => {
+            error!("Failed to persist allowlist changes: {}", e);
+            Err(AdminError {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                message: format!("allowlist updated but not persisted: {}", e),
+            })
+        }
+    }
+}
+
+/// Add observed ASNs to the allowlist
+async fn promote(
+    state: &AppState,
+    request: PromoteRequest,
+) -> Result<Json<AdminAsnsResponse>, AdminError> {
+    let Some(ref observer) = state.asn_observer else {
+        return Err(AdminError {
+            status: StatusCode::NOT_FOUND,
+            message: "learn mode is disabled, set ASN_LEARN_WINDOW_HOURS to enable it".to_string(),
+        });
+    };
+    let observed: Vec<String> = observer.observed().into_iter().map(|o| o.asn).collect();
+
+    let asns = if request.asns.is_empty() {
+        // Skip anything a provider reported that is not a real ASN, and home ISP
+        // ASNs seen during a leak
+        observed
+            .into_iter()
+            .filter(|asn| validate_asn(asn).is_ok())
+            .filter(|asn| {
+                let denied = state.policy.denied_asns.contains(asn);
+                if denied {
+                    warn!("Not promoting {}, it is in VPN_DENIED_ASNS", asn);
+                }
+                !denied
+            })
+            .collect()
+    } else {
+        let mut asns = Vec::with_capacity(request.asns.len());
+        for asn in &request.asns {
+            let asn = normalize_asn(asn)?;
+            if !observed.contains(&asn) {
+                return Err(AdminError {
+                    status: StatusCode::BAD_REQUEST,
+                    message: format!("{} has not been observed", asn),
+                });
+            }
+            asns.push(reject_denied(state, asn)?);
+        }
+        asns
+    };
+
+    let result = state
+        .allowed_asns
+        .add(&asns, state.admin_state_file.as_deref())
+        .await;
+    respond(state, result)
+}
+
+/// Check the bearer token against `ADMIN_TOKEN`
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), AdminError> {
+    let Some(ref token) = state.admin_token else {
+        return Err(AdminError {
+            status: StatusCode::NOT_FOUND,
+            message: "admin API is disabled, set ADMIN_TOKEN to enable it".to_string(),
+        });
+    };
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(provided) if constant_time_eq(provided.trim().as_bytes(), token.as_bytes()) => Ok(()),
+        _ => Err(AdminError {
+            status: StatusCode::UNAUTHORIZED,
+            message: "missing or invalid bearer token".to_string(),
+        }),
+    }
+}
+
+/// Uppercase and validate an ASN from a request body
+fn normalize_asn(asn: &str) -> Result<String, AdminError> {
+    let asn = asn.trim().to_uppercase();
+    validate_asn(&asn)
+        .map(|_| asn)
+        .map_err(|message| AdminError {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        })
+}
+
+/// Refuse home ISP ASNs, which would make a leak look healthy
+fn reject_denied(state: &AppState, asn: String) -> Result<String, AdminError> {
+    if state.policy.denied_asns.contains(&asn) {
+        return Err(AdminError {
+            status: StatusCode::CONFLICT,
+            message: format!("{} is a home ISP ASN in VPN_DENIED_ASNS", asn),
+        });
+    }
+    Ok(asn)
+}
+
+fn allowlist_response(state: &AppState, persisted: bool) -> AdminAsnsResponse {
+    let overrides = state.allowed_asns.overrides();
+    AdminAsnsResponse {
+        allowed_asns: state.allowed_asns.sorted(),
+        added: overrides.added.into_iter().collect(),
+        removed: overrides.removed.into_iter().collect(),
+        persisted,
+    }
+}
+
+/// Compare secrets without leaking the position of the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
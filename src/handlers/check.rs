@@ -1,70 +1,42 @@
 //! Health Check Handler
 //!
 //! Provides the `/check` endpoint for health monitoring.
-//! Returns 200 OK if VPN is connected with allowed ASN, 503 otherwise.
+//! Evaluates the configured policy rules (see [`crate::policy`]) and returns
+//! every rule result. The HTTP status follows the worst one: 200 OK when all
+//! rules pass or only warn, 503 when any rule fails.
+//! The messages of the worst results are joined in `reason`.
 //! Designed for Uptime Kuma and other monitoring tools.
+//! Uses the shared lookup cache unless `?fresh=1` is given.
 
-use crate::{
-    ip_lookup,
-    models::{AppState, CheckResponse},
+use crate::models::{AppState, CheckResponse, LookupQuery};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 
 /// Handler for /check endpoint
-pub async fn check_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let info = ip_lookup::lookup(
-        &state.client,
-        state.gluetun_url.as_deref(),
-        state.gluetun_api_key.as_deref(),
-    )
-    .await;
-
-    // Check for lookup errors
-    if info.error.is_some() {
-        return (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(CheckResponse {
-                ok: false,
-                reason: None, // Error is in the flattened lookup
-                lookup: info,
-            }),
-        );
-    }
-
-    // Check if ASNs are configured
-    if state.allowed_asns.is_empty() {
-        let mut err_info = info;
-        err_info.error = Some("VPN_ALLOWED_ASNS not set".to_string());
-        return (
-            StatusCode::SERVICE_UNAVAILABLE,
-            Json(CheckResponse {
-                ok: false,
-                reason: None,
-                lookup: err_info,
-            }),
-        );
-    }
+pub async fn check_handler(
+    State(state): State<AppState>,
+    Query(query): Query<LookupQuery>,
+) -> impl IntoResponse {
+    let info = state.lookup(query.is_fresh()).await;
+    let report = state.policy.evaluate(&info, &state.allowed_asns.snapshot());
 
-    // Check if ASN is allowed
-    if let Some(asn) = &info.asn {
-        if state.allowed_asns.contains(asn) {
-            return (
-                StatusCode::OK,
-                Json(CheckResponse {
-                    ok: true,
-                    reason: None,
-                    lookup: info,
-                }),
-            );
-        }
-    }
+    let status = if report.passed() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
 
-    // ASN not allowed
     (
-        StatusCode::SERVICE_UNAVAILABLE,
+        status,
         Json(CheckResponse {
-            ok: false,
-            reason: Some("ASN not allowed".to_string()),
+            ok: report.passed(),
+            status: report.status,
+            reason: report.reason(),
+            rules: report.results,
             lookup: info,
         }),
     )
@@ -0,0 +1,21 @@
+//! Config Handler
+//!
+//! Provides the `/config` endpoint.
+//! Returns the merged effective configuration with secrets redacted.
+
+use crate::models::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+/// Handler for /config endpoint
+pub async fn config_handler(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(
+            state
+                .effective_config
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        ),
+    )
+}
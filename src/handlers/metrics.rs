@@ -0,0 +1,34 @@
+//! Metrics Handler
+//!
+//! Provides the `/metrics` endpoint for Prometheus scraping.
+//! Refreshes the status gauges from the last cached lookup before rendering.
+//! A scrape never starts a lookup: the snapshot is kept up to date by `/check`,
+//! `/status` and the background monitors, so the scrape interval does not set the
+//! lookup rate.
+
+use crate::{allowlist::AsnVerdict, metrics, models::AppState};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+
+/// Handler for /metrics endpoint
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    if let Some(info) = state.lookup_cache.peek() {
+        let allowed_asns = state.allowed_asns.snapshot();
+        let verdict = AsnVerdict::of(
+            info.asn.as_deref(),
+            &allowed_asns,
+            &state.policy.denied_asns,
+        );
+        let report = state.policy.evaluate(&info, &allowed_asns);
+        metrics::record_status(&info, verdict, report.passed());
+    }
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
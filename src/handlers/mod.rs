@@ -1,10 +1,20 @@
 //! Handlers Module
 //!
 //! HTTP request handlers for the API endpoints.
-//! Exports the status and check handlers for use in the main router.
+//! Exports the status, check, metrics, config, observed ASNs and admin handlers for use in the main router.
 
+mod admin;
 mod check;
+mod config;
+mod metrics;
+mod observed;
 mod status;
 
+pub use admin::{
+    admin_add_handler, admin_list_handler, admin_promote_handler, admin_remove_handler,
+};
 pub use check::check_handler;
+pub use config::config_handler;
+pub use metrics::metrics_handler;
+pub use observed::observed_asns_handler;
 pub use status::status_handler;
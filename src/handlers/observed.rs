@@ -0,0 +1,45 @@
+//! Observed ASNs Handler
+//!
+//! Provides the `/asns/observed` endpoint for learn mode.
+//! Lists every ASN seen within the learn window with first/last seen times,
+//! lookup counts and whether it is already allowed.
+//! Returns 404 when learn mode is disabled.
+
+use crate::models::{AppState, ErrorResponse, ObservedAsnEntry, ObservedAsnsResponse};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Handler for /asns/observed endpoint
+pub async fn observed_asns_handler(State(state): State<AppState>) -> Response {
+    let Some(ref observer) = state.asn_observer else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "learn mode is disabled, set ASN_LEARN_WINDOW_HOURS to enable it"
+                    .to_string(),
+            }),
+        )
+            .into_response();
+    };
+
+    let allowed_asns = state.allowed_asns.snapshot();
+    let observed = observer
+        .observed()
+        .into_iter()
+        .map(|observed| ObservedAsnEntry {
+            allowed: allowed_asns.contains(&observed.asn),
+            observed,
+        })
+        .collect();
+
+    let response = ObservedAsnsResponse {
+        window_hours: observer.window_hours(),
+        observed,
+    };
+
+    (StatusCode::OK, Json(response)).into_response()
+}
@@ -2,30 +2,41 @@
 //!
 //! Provides the `/status` endpoint for informational monitoring.
 //! Always returns 200 OK with current VPN status and configuration.
+//! Uses the shared lookup cache unless `?fresh=1` is given.
 
-use crate::{
-    ip_lookup,
-    models::{AppState, StatusResponse},
+use crate::models::{AppState, LookupQuery, StatusResponse};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 
 /// Handler for /status endpoint
-pub async fn status_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let lookup = ip_lookup::lookup(
-        &state.client,
-        state.gluetun_url.as_deref(),
-        state.gluetun_api_key.as_deref(),
-    )
-    .await;
+pub async fn status_handler(
+    State(state): State<AppState>,
+    Query(query): Query<LookupQuery>,
+) -> impl IntoResponse {
+    let lookup = state.lookup(query.is_fresh()).await;
 
-    let mut allowed_vec: Vec<String> = state.allowed_asns.iter().cloned().collect();
-    allowed_vec.sort();
+    let allowed_vec = state.allowed_asns.sorted();
+    let cidrs: Vec<String> = state
+        .policy
+        .allowed_cidrs
+        .iter()
+        .map(ToString::to_string)
+        .collect();
 
-    let configured = !state.allowed_asns.is_empty();
+    let configured = !allowed_vec.is_empty() || !cidrs.is_empty();
+
+    let mut denied_vec: Vec<String> = state.policy.denied_asns.iter().cloned().collect();
+    denied_vec.sort();
 
     let response = StatusResponse {
         lookup,
         allowed_asns: allowed_vec,
+        denied_asns: denied_vec,
+        allowed_cidrs: cidrs,
         configured,
     };
 
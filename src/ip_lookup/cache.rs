@@ -0,0 +1,237 @@
+//! Lookup Cache
+//!
+//! Shares a single lookup snapshot between the HTTP handlers and background tasks.
+//! Avoids hammering public providers when `/check` is polled frequently.
+//! Concurrent callers wait for one in-flight lookup instead of starting their own.
+//! The DNS leak probe, the kill switch baseline and the proxy reachability check,
+//! when enabled, run alongside each live lookup and are cached with it.
+//! The tunnel state is never cached: Gluetun is asked on every call, so a stopped
+//! tunnel shows up even while an earlier lookup is still served. It is marked as
+//! `probing` while the kill switch probe stops the tunnel on purpose.
+//! [`LookupCache::peek`] reads the last snapshot without ever starting a lookup.
+
+use super::{
+    consensus, lookup, race, DualStack, GluetunProvider, IpLookupProvider, LookupMode, LookupProxy,
+};
+use crate::{
+    dns::DnsProbe,
+    killswitch::KillSwitch,
+    models::{DnsReport, KillSwitchStatus, LookupResult, ProxyStatus, TunnelStatus},
+};
+use reqwest::Client;
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// Most recent lookup result with a time-to-live
+pub struct LookupCache {
+    ttl: Duration,
+    mode: LookupMode,
+    dual_stack: Option<DualStack>,
+    dns_probe: Option<DnsProbe>,
+    kill_switch: Option<KillSwitch>,
+    proxy: Option<LookupProxy>,
+    tunnel: Option<GluetunProvider>,
+    snapshot: Mutex<Option<(Instant, LookupResult)>>,
+    /// Tunnel state seen by the most recent `get`
+    last_tunnel: RwLock<Option<TunnelStatus>>,
+}
+
+impl LookupCache {
+    /// Create an empty cache, a zero TTL disables caching
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            mode: LookupMode::default(),
+            dual_stack: None,
+            dns_probe: None,
+            kill_switch: None,
+            proxy: None,
+            tunnel: None,
+            snapshot: Mutex::new(None),
+            last_tunnel: RwLock::new(None),
+        }
+    }
+
+    /// Query providers with `mode` instead of the fallback chain
+    pub fn with_mode(mut self, mode: LookupMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Run the chain over IPv4 only, with a separate IPv6 lookup alongside
+    pub fn with_dual_stack(mut self, dual_stack: DualStack) -> Self {
+        self.dual_stack = Some(dual_stack);
+        self
+    }
+
+    /// Run the DNS leak probe with every live lookup
+    pub fn with_dns_probe(mut self, probe: DnsProbe) -> Self {
+        self.dns_probe = Some(probe);
+        self
+    }
+
+    /// Send the provider chain through a proxy and check that it is reachable
+    pub fn with_proxy(mut self, proxy: LookupProxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Compare Gluetun's exit IP with the home IP on every live lookup
+    pub fn with_kill_switch(mut self, kill_switch: KillSwitch) -> Self {
+        self.kill_switch = Some(kill_switch);
+        self
+    }
+
+    /// Ask Gluetun for the tunnel state on every call, cached result or not
+    pub fn with_tunnel_status(mut self, gluetun: GluetunProvider) -> Self {
+        self.tunnel = Some(gluetun);
+        self
+    }
+
+    /// Kill switch verification, for the tunnel-stop probe
+    pub fn kill_switch(&self) -> Option<&KillSwitch> {
+        self.kill_switch.as_ref()
+    }
+
+    /// Return the cached result if younger than the TTL, otherwise perform a live lookup
+    ///
+    /// `fresh` forces a live lookup; its result still replaces the cached snapshot.
+    /// Cached results carry their age in `cache_age_secs`.
+    pub async fn get(
+        &self,
+        client: &Client,
+        providers: &[Box<dyn IpLookupProvider>],
+        fresh: bool,
+    ) -> LookupResult {
+        let (mut result, tunnel) = tokio::join!(
+            self.snapshot_or_lookup(client, providers, fresh),
+            self.tunnel_status(client)
+        );
+        *self.last_tunnel.write().unwrap_or_else(|e| e.into_inner()) = tunnel.clone();
+        result.tunnel = tunnel;
+        result
+    }
+
+    /// Last snapshot whatever its age, without starting a lookup
+    ///
+    /// `None` before the first lookup and while one is in flight.
+    pub fn peek(&self) -> Option<LookupResult> {
+        let snapshot = self.snapshot.try_lock().ok()?;
+        let (taken_at, result) = snapshot.as_ref()?;
+        let mut cached = result.clone();
+        cached.cache_age_secs = Some(taken_at.elapsed().as_secs());
+        cached.tunnel = self
+            .last_tunnel
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        Some(cached)
+    }
+
+    async fn snapshot_or_lookup(
+        &self,
+        client: &Client,
+        providers: &[Box<dyn IpLookupProvider>],
+        fresh: bool,
+    ) -> LookupResult {
+        let mut snapshot = self.snapshot.lock().await;
+
+        if !fresh {
+            if let Some((taken_at, result)) = snapshot.as_ref() {
+                let age = taken_at.elapsed();
+                if age < self.ttl {
+                    let mut cached = result.clone();
+                    cached.cache_age_secs = Some(age.as_secs());
+                    return cached;
+                }
+            }
+        }
+
+        let (mut result, dns, kill_switch, proxy) = tokio::join!(
+            self.lookup(client, providers),
+            self.probe_dns(),
+            self.verify_kill_switch(client),
+            self.check_proxy()
+        );
+        result.dns = dns;
+        result.kill_switch = kill_switch;
+        result.proxy = proxy;
+        *snapshot = Some((Instant::now(), result.clone()));
+        result
+    }
+
+    /// Live lookup, through the proxy or split by address family in dual-stack mode
+    async fn lookup(
+        &self,
+        client: &Client,
+        providers: &[Box<dyn IpLookupProvider>],
+    ) -> LookupResult {
+        // Config validation rejects IP_LOOKUP_PROXY together with IP_LOOKUP_DUAL_STACK
+        debug_assert!(
+            self.proxy.is_none() || self.dual_stack.is_none(),
+            "a lookup proxy and dual-stack lookups are mutually exclusive"
+        );
+        if let Some(ref proxy) = self.proxy {
+            return self.query(proxy.client(), providers).await;
+        }
+        match self.dual_stack {
+            Some(ref dual_stack) => {
+                let (mut result, ipv6) = tokio::join!(
+                    self.query(dual_stack.ipv4_client(), providers),
+                    dual_stack.lookup_ipv6()
+                );
+                result.ipv6 = Some(ipv6);
+                result
+            }
+            None => self.query(client, providers).await,
+        }
+    }
+
+    async fn probe_dns(&self) -> Option<DnsReport> {
+        match self.dns_probe {
+            Some(ref probe) => Some(probe.run().await),
+            None => None,
+        }
+    }
+
+    async fn check_proxy(&self) -> Option<ProxyStatus> {
+        match self.proxy {
+            Some(ref proxy) => Some(proxy.check().await),
+            None => None,
+        }
+    }
+
+    async fn tunnel_status(&self, client: &Client) -> Option<TunnelStatus> {
+        match self.tunnel {
+            Some(ref gluetun) => {
+                let mut status = gluetun.tunnel_status(client).await;
+                status.probing = self.kill_switch.as_ref().is_some_and(KillSwitch::probing);
+                Some(status)
+            }
+            None => None,
+        }
+    }
+
+    async fn verify_kill_switch(&self, client: &Client) -> Option<KillSwitchStatus> {
+        match self.kill_switch {
+            Some(ref kill_switch) => Some(kill_switch.verify(client).await),
+            None => None,
+        }
+    }
+
+    /// Query the providers according to the lookup mode
+    async fn query(
+        &self,
+        client: &Client,
+        providers: &[Box<dyn IpLookupProvider>],
+    ) -> LookupResult {
+        match self.mode {
+            LookupMode::Fallback => lookup(client, providers).await,
+            LookupMode::Race => race(client, providers).await,
+            LookupMode::Consensus => consensus(client, providers).await,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Dual-Stack Lookup
+//!
+//! Looks up the IPv4 and IPv6 exit separately by forcing the local address family
+//! of each request, instead of learning whichever family reqwest happens to use.
+//! - IPv4: the regular provider chain, over a client bound to `0.0.0.0`
+//! - IPv6: `IPV6_LOOKUP_PROVIDERS`, over a client bound to `::`
+//!
+//! An IPv6 answer means IPv6 traffic reaches the internet; the `ipv6_leak` rule
+//! checks that it goes through the VPN. No answer is the expected state when
+//! IPv6 is blocked, so failures are only logged at debug level.
+
+use super::{build_providers, AsnDatabase, IpLookupProvider};
+use crate::models::Ipv6Lookup;
+use reqwest::Client;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::debug;
+
+/// Default `IPV6_LOOKUP_PROVIDERS`, public services reachable over IPv6
+pub const DEFAULT_IPV6_PROVIDERS: [&str; 2] = ["ifconfig", "ipapi"];
+
+/// Clients pinned to each address family and the IPv6 provider chain
+pub struct DualStack {
+    ipv4: Client,
+    ipv6: Client,
+    ipv6_providers: Vec<Box<dyn IpLookupProvider>>,
+}
+
+impl DualStack {
+    /// Build both clients with the given request timeout
+    pub fn new(
+        timeout: Duration,
+        ipv6_providers: Vec<Box<dyn IpLookupProvider>>,
+    ) -> Result<Self, String> {
+        let client = |local: IpAddr| {
+            Client::builder()
+                .timeout(timeout)
+                .local_address(local)
+                .build()
+                .map_err(|e| format!("failed to create HTTP client: {}", e))
+        };
+
+        Ok(Self {
+            ipv4: client(IpAddr::V4(Ipv4Addr::UNSPECIFIED))?,
+            ipv6: client(IpAddr::V6(Ipv6Addr::UNSPECIFIED))?,
+            ipv6_providers,
+        })
+    }
+
+    /// Client that only connects over IPv4, for the regular provider chain
+    pub fn ipv4_client(&self) -> &Client {
+        &self.ipv4
+    }
+
+    /// Ask the IPv6 providers in order, the first IPv6 answer wins
+    pub async fn lookup_ipv6(&self) -> Ipv6Lookup {
+        let mut failures = Vec::new();
+
+        for provider in &self.ipv6_providers {
+            match provider.fetch(&self.ipv6).await {
+                Ok(res) => {
+                    let addr = res.ip.as_deref().and_then(|ip| ip.parse::<IpAddr>().ok());
+                    match addr.map(|addr| addr.to_canonical()) {
+                        Some(IpAddr::V6(addr)) => {
+                            debug!("IPv6 exit {} answered by '{}'", addr, provider.name());
+                            return Ipv6Lookup {
+                                ip: Some(addr.to_string()),
+                                asn: res.asn,
+                                org: res.org,
+                                source: Some(provider.name().to_string()),
+                                error: None,
+                            };
+                        }
+                        _ => failures.push(format!(
+                            "{}: answered {} instead of an IPv6 address",
+                            provider.name(),
+                            res.ip.as_deref().unwrap_or("no IP")
+                        )),
+                    }
+                }
+                Err(e) => failures.push(format!("{}: {}", provider.name(), e)),
+            }
+        }
+
+        let summary = failures.join("; ");
+        debug!("IPv6 exit not reachable: {}", summary);
+        Ipv6Lookup {
+            ip: None,
+            asn: None,
+            org: None,
+            source: None,
+            error: Some(format!("IPv6 not reachable ({})", summary)),
+        }
+    }
+}
+
+/// Build the IPv6 provider chain from `IPV6_LOOKUP_PROVIDERS` entries
+///
+/// `gluetun` is rejected: its control server reports a single public IP.
+/// `offline` needs an explicit URL answering over IPv6.
+pub fn build_ipv6_providers(
+    specs: &[String],
+    timeouts: &HashMap<String, u64>,
+    asn_database: Option<&Arc<AsnDatabase>>,
+) -> Result<Vec<Box<dyn IpLookupProvider>>, String> {
+    if let Some(spec) = specs
+        .iter()
+        .find(|spec| spec.trim().to_lowercase().starts_with("gluetun"))
+    {
+        return Err(format!(
+            "'{}': Gluetun reports a single public IP and cannot look up the IPv6 exit",
+            spec
+        ));
+    }
+    build_providers(specs, None, None, timeouts, asn_database)
+}
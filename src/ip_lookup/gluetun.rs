@@ -3,8 +3,13 @@
 //! Fetches IP information directly from the Gluetun API.
 //! Primary lookup source when Gluetun API is configured.
 //! Provides port forwarding information if available.
+//! Reports the tunnel state from `/v1/vpn/status`, or `/v1/openvpn/status` on
+//! versions before the VPN-agnostic endpoint.
+//! Can also start and stop the VPN through the control server (used by `discover-asns`).
 
-use crate::models::LookupResult;
+use super::{get_json, missing, IpLookupProvider};
+use crate::models::{LookupError, LookupResult, TunnelState, TunnelStatus};
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
 
@@ -17,23 +22,67 @@ struct GluetunResponse {
     organization: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct StatusResponse {
+    status: TunnelState,
+}
+
 #[derive(Deserialize)]
 struct PortResponse {
     port: Option<u16>,
 }
 
+/// Gluetun control server lookup provider
+pub struct GluetunProvider {
+    url: String,
+    api_key: Option<String>,
+}
+
+impl GluetunProvider {
+    pub fn new(url: &str, api_key: Option<&str>) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(str::to_string),
+        }
+    }
+
+    /// Current tunnel state, with the error when the control server did not answer
+    pub async fn tunnel_status(&self, client: &Client) -> TunnelStatus {
+        match fetch_vpn_status(client, &self.url, self.api_key.as_deref()).await {
+            Ok(state) => TunnelStatus {
+                state: Some(state),
+                error: None,
+                probing: false,
+            },
+            Err(e) => TunnelStatus {
+                state: None,
+                error: Some(e.to_string()),
+                probing: false,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl IpLookupProvider for GluetunProvider {
+    fn name(&self) -> &str {
+        "gluetun"
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError> {
+        fetch_gluetun_ip(client, &self.url, self.api_key.as_deref()).await
+    }
+}
+
 /// Fetch IP information from Gluetun API
 pub async fn fetch_gluetun_ip(
     client: &Client,
     gluetun_url: &str,
     api_key: Option<&str>,
-) -> Option<LookupResult> {
-    let url = format!("{}/v1/publicip/ip", gluetun_url);
+) -> Result<LookupResult, LookupError> {
+    let data = fetch_public_ip_info(client, gluetun_url).await?;
 
-    let resp = client.get(&url).send().await.ok()?;
-    let data: GluetunResponse = resp.json().await.ok()?;
-
-    let ip = data.public_ip?;
+    let ip = data.public_ip.ok_or_else(|| missing("public_ip"))?;
 
     // Extract ASN from organization field (format: "AS212238 Datacamp Limited")
     let (asn, org) = parse_organization(&data.organization);
@@ -41,7 +90,7 @@ pub async fn fetch_gluetun_ip(
     // Fetch port forwarding info
     let port_forwarded = fetch_port_forwarded(client, gluetun_url, api_key).await;
 
-    Some(LookupResult {
+    Ok(LookupResult {
         ip: Some(ip),
         asn,
         org,
@@ -49,10 +98,77 @@ pub async fn fetch_gluetun_ip(
         city: data.city,
         region: data.region,
         port_forwarded,
-        error: None,
+        ..Default::default()
     })
 }
 
+/// Fetch only Gluetun's exit IP, without asking for the forwarded port
+pub async fn fetch_gluetun_exit_ip(
+    client: &Client,
+    gluetun_url: &str,
+) -> Result<String, LookupError> {
+    fetch_public_ip_info(client, gluetun_url)
+        .await?
+        .public_ip
+        .ok_or_else(|| missing("public_ip"))
+}
+
+async fn fetch_public_ip_info(
+    client: &Client,
+    gluetun_url: &str,
+) -> Result<GluetunResponse, LookupError> {
+    let url = format!("{}/v1/publicip/ip", gluetun_url);
+    get_json(client.get(&url)).await
+}
+
+/// Fetch the tunnel state, falling back to the OpenVPN endpoint of older Gluetun versions
+pub async fn fetch_vpn_status(
+    client: &Client,
+    gluetun_url: &str,
+    api_key: Option<&str>,
+) -> Result<TunnelState, LookupError> {
+    let request = |path: &str| {
+        let request = client.get(format!("{}{}", gluetun_url, path));
+        match api_key {
+            Some(key) => request.header("X-API-Key", key),
+            None => request,
+        }
+    };
+
+    let data: StatusResponse = match get_json(request("/v1/vpn/status")).await {
+        Err(LookupError::HttpStatus { status: 404 }) => {
+            get_json(request("/v1/openvpn/status")).await?
+        }
+        result => result?,
+    };
+    Ok(data.status)
+}
+
+/// Set the VPN status (`"running"` or `"stopped"`) through the Gluetun control server
+pub async fn set_vpn_status(
+    client: &Client,
+    gluetun_url: &str,
+    api_key: Option<&str>,
+    status: &str,
+) -> Result<(), LookupError> {
+    let url = format!("{}/v1/vpn/status", gluetun_url);
+
+    let mut request = client
+        .put(&url)
+        .json(&serde_json::json!({ "status": status }));
+    if let Some(key) = api_key {
+        request = request.header("X-API-Key", key);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(LookupError::HttpStatus {
+            status: response.status().as_u16(),
+        });
+    }
+    Ok(())
+}
+
 /// Parse organization string to extract ASN and org name
 pub fn parse_organization(org_str: &Option<String>) -> (Option<String>, Option<String>) {
     let org_str = match org_str {
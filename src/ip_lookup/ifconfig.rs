@@ -1,9 +1,11 @@
 //! ifconfig.co IP Lookup
 //!
 //! Fast, simple JSON API for IP information.
-//! Second fallback source after Gluetun API.
+//! Also works with self-hosted echoip instances, which share the same JSON format.
 
-use crate::models::LookupResult;
+use super::{get_json, missing, IpLookupProvider};
+use crate::models::{LookupError, LookupResult};
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
 
@@ -16,19 +18,40 @@ struct IfConfigResponse {
     country: Option<String>,
 }
 
-/// Fetch IP information from ifconfig.co
-pub async fn fetch_ifconfig(client: &Client) -> Option<LookupResult> {
-    let resp = client
-        .get("https://ifconfig.co/json")
-        .header("Accept", "application/json")
-        .send()
-        .await
-        .ok()?;
+/// Default ifconfig.co endpoint
+pub const DEFAULT_URL: &str = "https://ifconfig.co/json";
+
+/// ifconfig.co (echoip) lookup provider
+pub struct IfconfigProvider {
+    url: String,
+}
+
+impl IfconfigProvider {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
 
-    let data: IfConfigResponse = resp.json().await.ok()?;
+#[async_trait]
+impl IpLookupProvider for IfconfigProvider {
+    fn name(&self) -> &str {
+        "ifconfig"
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError> {
+        fetch_ifconfig(client, &self.url).await
+    }
+}
+
+/// Fetch IP information from ifconfig.co
+pub async fn fetch_ifconfig(client: &Client, url: &str) -> Result<LookupResult, LookupError> {
+    let data: IfConfigResponse =
+        get_json(client.get(url).header("Accept", "application/json")).await?;
 
-    let ip = data.ip?;
-    let asn = data.asn?;
+    let ip = data.ip.ok_or_else(|| missing("ip"))?;
+    let asn = data.asn.ok_or_else(|| missing("asn"))?;
 
     // Ensure ASN has "AS" prefix
     let asn_formatted = if asn.to_uppercase().starts_with("AS") {
@@ -39,14 +62,11 @@ pub async fn fetch_ifconfig(client: &Client) -> Option<LookupResult> {
 
     let org = data.asn_org.or(data.org);
 
-    Some(LookupResult {
+    Ok(LookupResult {
         ip: Some(ip),
         asn: Some(asn_formatted),
         org,
         country: data.country,
-        city: None,
-        region: None,
-        port_forwarded: None,
-        error: None,
+        ..Default::default()
     })
 }
@@ -1,9 +1,11 @@
 //! ip-api.com IP Lookup
 //!
 //! Detailed geolocation and ASN information.
-//! Third fallback source with comprehensive data.
+//! Fallback source with comprehensive data.
 
-use crate::models::LookupResult;
+use super::{get_json, missing, IpLookupProvider};
+use crate::models::{LookupError, LookupResult};
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
 
@@ -16,32 +18,54 @@ struct IpApiResponse {
     country_name: Option<String>,
 }
 
-/// Fetch IP information from ipapi.co
-pub async fn fetch_ipapi(client: &Client) -> Option<LookupResult> {
-    let resp = client.get("https://ipapi.co/json/").send().await.ok()?;
+/// Default ipapi.co endpoint
+pub const DEFAULT_URL: &str = "https://ipapi.co/json/";
+
+/// ipapi.co lookup provider
+pub struct IpapiProvider {
+    url: String,
+}
+
+impl IpapiProvider {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
 
-    let data: IpApiResponse = resp.json().await.ok()?;
+#[async_trait]
+impl IpLookupProvider for IpapiProvider {
+    fn name(&self) -> &str {
+        "ipapi"
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError> {
+        fetch_ipapi(client, &self.url).await
+    }
+}
+
+/// Fetch IP information from ipapi.co
+pub async fn fetch_ipapi(client: &Client, url: &str) -> Result<LookupResult, LookupError> {
+    let data: IpApiResponse = get_json(client.get(url)).await?;
 
-    let ip = data.ip?;
-    let asn_val = data.asn?;
+    let ip = data.ip.ok_or_else(|| missing("ip"))?;
+    let asn_val = data.asn.ok_or_else(|| missing("asn"))?;
 
     // Handle ASN as either string or number
     let asn_str = match asn_val {
         serde_json::Value::String(s) => s,
         serde_json::Value::Number(n) => n.to_string(),
-        _ => return None,
+        _ => return Err(missing("asn")),
     };
 
     let org = data.org.or(data.organization);
 
-    Some(LookupResult {
+    Ok(LookupResult {
         ip: Some(ip),
         asn: Some(asn_str.to_uppercase()),
         org,
         country: data.country_name,
-        city: None,
-        region: None,
-        port_forwarded: None,
-        error: None,
+        ..Default::default()
     })
 }
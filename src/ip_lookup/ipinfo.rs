@@ -0,0 +1,66 @@
+//! ipinfo.io IP Lookup
+//!
+//! Geolocation with ASN embedded in the `org` field ("AS12345 Provider Name").
+//! Optional fallback source, not part of the default chain.
+
+use super::{get_json, gluetun::parse_organization, missing, IpLookupProvider};
+use crate::models::{LookupError, LookupResult};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+/// Default ipinfo.io endpoint
+pub const DEFAULT_URL: &str = "https://ipinfo.io/json";
+
+#[derive(Deserialize)]
+struct IpInfoResponse {
+    ip: Option<String>,
+    org: Option<String>,
+    country: Option<String>,
+    region: Option<String>,
+    city: Option<String>,
+}
+
+/// ipinfo.io lookup provider
+pub struct IpinfoProvider {
+    url: String,
+}
+
+impl IpinfoProvider {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl IpLookupProvider for IpinfoProvider {
+    fn name(&self) -> &str {
+        "ipinfo"
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError> {
+        fetch_ipinfo(client, &self.url).await
+    }
+}
+
+/// Fetch IP information from ipinfo.io
+pub async fn fetch_ipinfo(client: &Client, url: &str) -> Result<LookupResult, LookupError> {
+    let data: IpInfoResponse =
+        get_json(client.get(url).header("Accept", "application/json")).await?;
+
+    let ip = data.ip.ok_or_else(|| missing("ip"))?;
+    let (asn, org) = parse_organization(&data.org);
+    let asn = asn.ok_or_else(|| missing("org"))?;
+
+    Ok(LookupResult {
+        ip: Some(ip),
+        asn: Some(asn),
+        org,
+        country: data.country,
+        city: data.city,
+        region: data.region,
+        ..Default::default()
+    })
+}
@@ -3,57 +3,483 @@
 //! Handles fetching the current public IP address and related information (ASN, country, etc.)
 //! from multiple sources with fallback support.
 //!
-//! ## Lookup Sources (in order)
-//! 1. Gluetun API (if configured) - Primary source with port forwarding info
-//! 2. ifconfig.co - Fast, simple JSON API
-//! 3. ip-api.com - Detailed geolocation and ASN information
+//! ## Lookup Sources
+//! Each source implements [`IpLookupProvider`]. The chain is built at startup from
+//! `IP_LOOKUP_PROVIDERS` and tried in order until one answers. Built-in providers:
+//! - `gluetun` - Gluetun API, primary source with port forwarding info
+//! - `ifconfig` - ifconfig.co (or any self-hosted echoip instance)
+//! - `ipapi` - ipapi.co
+//! - `ipinfo` - ipinfo.io
+//! - `offline` - local IP-to-ASN database (`ASN_DATABASE`), with the IP from Gluetun
+//!
+//! Any provider can be pointed at a different endpoint with `name=url`,
+//! e.g. `ifconfig=http://echo.lan/json`. For `offline` the URL answers the public IP.
+//!
+//! Other modes (`IP_LOOKUP_MODE`) query every provider concurrently instead:
+//! - `race`: the first answer by chain order is used as soon as every provider before
+//!   it has failed, so a hung provider costs only its timeout rather than the whole chain
+//! - `consensus`: the first answer by chain order is used, and the others are compared
+//!   with it so a stale or wrong IP/ASN from one source shows up as a disagreement
+//!
+//! With `IP_LOOKUP_DUAL_STACK` the chain is forced over IPv4 and a separate IPv6 lookup
+//! runs alongside it (see [`DualStack`]). With `IP_LOOKUP_PROXY` the chain goes through
+//! Gluetun's HTTP proxy or a SOCKS5 proxy instead (see [`LookupProxy`]).
+//!
+//! Callers normally go through [`LookupCache`] so results are shared for `LOOKUP_CACHE_TTL_SECONDS`.
 
+mod cache;
+mod dual_stack;
 pub mod gluetun; // Public for testing
 mod ifconfig;
 mod ipapi;
+mod ipinfo;
+mod offline;
+mod proxy;
 
-use crate::models::LookupResult;
-use reqwest::Client;
-use tracing::error;
+use crate::metrics;
+use crate::models::{Consensus, LookupAttempt, LookupError, LookupResult, ProviderAnswer};
+use async_trait::async_trait;
+use futures_util::{future::join_all, stream::FuturesUnordered, StreamExt};
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, warn};
 
-pub use gluetun::fetch_gluetun_ip;
-pub use ifconfig::fetch_ifconfig;
-pub use ipapi::fetch_ipapi;
+pub use cache::LookupCache;
+pub use dual_stack::{build_ipv6_providers, DualStack, DEFAULT_IPV6_PROVIDERS};
+pub use gluetun::{
+    fetch_gluetun_exit_ip, fetch_gluetun_ip, fetch_vpn_status, set_vpn_status, GluetunProvider,
+};
+pub use ifconfig::{fetch_ifconfig, IfconfigProvider};
+pub use ipapi::{fetch_ipapi, IpapiProvider};
+pub use ipinfo::{fetch_ipinfo, IpinfoProvider};
+pub use offline::{fetch_public_ip, AsnDatabase, AsnRecord, OfflineProvider};
+pub use proxy::{parse_proxy_url, LookupProxy};
 
-/// Perform IP lookup with fallback strategy
+/// A source of public IP / ASN information
+#[async_trait]
+pub trait IpLookupProvider: Send + Sync {
+    /// Short provider name used in configuration and logs
+    fn name(&self) -> &str;
+
+    /// Fetch the current public IP information
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError>;
+}
+
+/// How the provider chain is queried
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LookupMode {
+    /// Try providers in order until one answers
+    #[default]
+    Fallback,
+    /// Query every provider concurrently, use the first answer by chain order
+    Race,
+    /// Query every provider concurrently and compare their answers
+    Consensus,
+}
+
+impl LookupMode {
+    /// Name used in `IP_LOOKUP_MODE`
+    pub fn name(self) -> &'static str {
+        match self {
+            LookupMode::Fallback => "fallback",
+            LookupMode::Race => "race",
+            LookupMode::Consensus => "consensus",
+        }
+    }
+}
+
+impl fmt::Display for LookupMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for LookupMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "fallback" => Ok(LookupMode::Fallback),
+            "race" => Ok(LookupMode::Race),
+            "consensus" => Ok(LookupMode::Consensus),
+            other => Err(format!(
+                "unknown lookup mode '{}' (expected fallback, race or consensus)",
+                other
+            )),
+        }
+    }
+}
+
+/// Send a request and decode its JSON body, mapping failures to `LookupError`
+pub(crate) async fn get_json<T: DeserializeOwned>(
+    request: RequestBuilder,
+) -> Result<T, LookupError> {
+    let resp = request.send().await?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(LookupError::HttpStatus {
+            status: status.as_u16(),
+        });
+    }
+
+    let body = resp.text().await?;
+    serde_json::from_str(&body).map_err(|e| LookupError::Decode {
+        message: e.to_string(),
+    })
+}
+
+/// Shorthand for a `LookupError::MissingField`
+pub(crate) fn missing(field: &str) -> LookupError {
+    LookupError::MissingField {
+        field: field.to_string(),
+    }
+}
+
+/// Provider wrapper that fails with `LookupError::Timeout` after a fixed duration
+struct TimedProvider {
+    inner: Box<dyn IpLookupProvider>,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl IpLookupProvider for TimedProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError> {
+        tokio::time::timeout(self.timeout, self.inner.fetch(client))
+            .await
+            .unwrap_or(Err(LookupError::Timeout))
+    }
+}
+
+/// Key in the timeout map applying to every provider without its own
+pub const DEFAULT_TIMEOUT: &str = "default";
+
+/// Build the provider chain from `IP_LOOKUP_PROVIDERS` entries
 ///
-/// Tries Gluetun API first (if configured), then falls back to external services
-pub async fn lookup(
-    client: &Client,
+/// Entries are either a built-in provider name or `name=url` to override its endpoint.
+/// The `gluetun` entry uses `GLUETUN_API_URL` unless a URL is given explicitly.
+/// `timeouts` maps provider names to a timeout in seconds, shorter than the client default;
+/// a `default` entry applies to providers without their own.
+/// The `offline` entry resolves against `asn_database` and gets the IP from Gluetun
+/// unless a URL is given.
+pub fn build_providers(
+    specs: &[String],
     gluetun_url: Option<&str>,
     api_key: Option<&str>,
-) -> LookupResult {
-    // Try Gluetun API first if available
-    if let Some(url) = gluetun_url {
-        if let Some(res) = fetch_gluetun_ip(client, url, api_key).await {
-            return res;
-        }
+    timeouts: &HashMap<String, u64>,
+    asn_database: Option<&Arc<AsnDatabase>>,
+) -> Result<Vec<Box<dyn IpLookupProvider>>, String> {
+    let mut providers: Vec<Box<dyn IpLookupProvider>> = Vec::with_capacity(specs.len());
+
+    for spec in specs {
+        let (name, url) = match spec.split_once('=') {
+            Some((name, url)) => (name.trim().to_lowercase(), Some(url.trim())),
+            None => (spec.trim().to_lowercase(), None),
+        };
+
+        let provider: Box<dyn IpLookupProvider> = match name.as_str() {
+            "gluetun" => {
+                let url = url.or(gluetun_url).ok_or_else(|| {
+                    "provider 'gluetun' requires GLUETUN_API_URL to be set".to_string()
+                })?;
+                Box::new(GluetunProvider::new(url, api_key))
+            }
+            "ifconfig" => Box::new(IfconfigProvider::new(url.unwrap_or(ifconfig::DEFAULT_URL))),
+            "ipapi" => Box::new(IpapiProvider::new(url.unwrap_or(ipapi::DEFAULT_URL))),
+            "ipinfo" => Box::new(IpinfoProvider::new(url.unwrap_or(ipinfo::DEFAULT_URL))),
+            "offline" => {
+                let database = asn_database.ok_or_else(|| {
+                    "provider 'offline' requires ASN_DATABASE to be set".to_string()
+                })?;
+                match (url, gluetun_url) {
+                    (Some(url), _) => Box::new(OfflineProvider::new(url, None, database.clone())),
+                    (None, Some(gluetun_url)) => Box::new(OfflineProvider::new(
+                        &format!("{}/v1/publicip/ip", gluetun_url.trim_end_matches('/')),
+                        api_key,
+                        database.clone(),
+                    )),
+                    (None, None) => {
+                        return Err("provider 'offline' requires GLUETUN_API_URL or an IP URL \
+                                    (offline=https://...)"
+                            .to_string())
+                    }
+                }
+            }
+            other => return Err(format!("unknown IP lookup provider '{}'", other)),
+        };
+
+        let provider = match timeouts
+            .get(&name)
+            .or_else(|| timeouts.get(DEFAULT_TIMEOUT))
+        {
+            Some(&secs) => Box::new(TimedProvider {
+                inner: provider,
+                timeout: Duration::from_secs(secs),
+            }),
+            None => provider,
+        };
+
+        providers.push(provider);
     }
 
-    // Fallback to external services
-    if let Some(res) = fetch_ifconfig(client).await {
-        return res;
+    if providers.is_empty() {
+        return Err("no IP lookup providers configured".to_string());
     }
 
-    if let Some(res) = fetch_ipapi(client).await {
-        return res;
+    Ok(providers)
+}
+
+/// Perform IP lookup with fallback strategy
+///
+/// Tries each provider in order and returns the first successful answer,
+/// tagged with the provider name, its latency and the lookup time.
+/// Failures of earlier providers are recorded in `LookupResult::attempts`.
+pub async fn lookup(client: &Client, providers: &[Box<dyn IpLookupProvider>]) -> LookupResult {
+    let checked_at = chrono::Utc::now();
+    let mut attempts = Vec::new();
+
+    for provider in providers {
+        let started = Instant::now();
+        let outcome = provider.fetch(client).await;
+        metrics::observe_lookup(provider.name(), started.elapsed());
+
+        match outcome {
+            Ok(mut res) => {
+                let latency_ms = started.elapsed().as_millis() as u64;
+                debug!(
+                    "IP lookup answered by '{}' in {} ms",
+                    provider.name(),
+                    latency_ms
+                );
+                res.source = Some(provider.name().to_string());
+                res.latency_ms = Some(latency_ms);
+                res.checked_at = Some(checked_at);
+                res.attempts = attempts;
+                return res;
+            }
+            Err(e) => {
+                warn!("IP lookup provider '{}' failed: {}", provider.name(), e);
+                metrics::lookup_failed(provider.name(), &e);
+                attempts.push(LookupAttempt {
+                    provider: provider.name().to_string(),
+                    error: e,
+                });
+            }
+        }
     }
 
     // All lookups failed
-    error!("All IP lookup services failed");
+    all_failed(checked_at, attempts)
+}
+
+/// Query every provider concurrently, returning the first answer by chain order
+///
+/// A provider's answer is used once every provider before it has failed, without
+/// waiting for the ones after it, which are then cancelled. The result is the same
+/// as [`lookup`] but takes as long as the slowest provider needed rather than the
+/// sum of their latencies.
+pub async fn race(client: &Client, providers: &[Box<dyn IpLookupProvider>]) -> LookupResult {
+    let checked_at = chrono::Utc::now();
+    let mut pending: FuturesUnordered<_> = providers
+        .iter()
+        .enumerate()
+        .map(|(index, provider)| async move {
+            let started = Instant::now();
+            let outcome = provider.fetch(client).await;
+            let elapsed = started.elapsed();
+            metrics::observe_lookup(provider.name(), elapsed);
+            (index, elapsed, outcome)
+        })
+        .collect();
+
+    let mut outcomes: Vec<Option<(Duration, Result<LookupResult, LookupError>)>> =
+        (0..providers.len()).map(|_| None).collect();
+    let mut attempts = Vec::new();
+    let mut next = 0;
+
+    while let Some((index, elapsed, outcome)) = pending.next().await {
+        outcomes[index] = Some((elapsed, outcome));
+
+        // Settle providers in chain order as far as they have finished
+        while let Some((elapsed, outcome)) = outcomes.get_mut(next).and_then(Option::take) {
+            let name = providers[next].name();
+            next += 1;
+            match outcome {
+                Ok(mut res) => {
+                    let latency_ms = elapsed.as_millis() as u64;
+                    debug!("IP lookup race won by '{}' in {} ms", name, latency_ms);
+                    res.source = Some(name.to_string());
+                    res.latency_ms = Some(latency_ms);
+                    res.checked_at = Some(checked_at);
+                    res.attempts = attempts;
+                    return res;
+                }
+                Err(e) => {
+                    warn!("IP lookup provider '{}' failed: {}", name, e);
+                    metrics::lookup_failed(name, &e);
+                    attempts.push(LookupAttempt {
+                        provider: name.to_string(),
+                        error: e,
+                    });
+                }
+            }
+        }
+    }
+
+    // All lookups failed
+    all_failed(checked_at, attempts)
+}
+
+/// Query every provider concurrently and compare their answers
+///
+/// The first successful answer in chain order is returned, as in [`lookup`],
+/// with every provider's answer in `LookupResult::consensus`. Any other provider
+/// seeing a different IP or ASN is listed as a disagreement.
+pub async fn consensus(client: &Client, providers: &[Box<dyn IpLookupProvider>]) -> LookupResult {
+    let checked_at = chrono::Utc::now();
+    let outcomes = join_all(providers.iter().map(|provider| async move {
+        let started = Instant::now();
+        let outcome = provider.fetch(client).await;
+        let elapsed = started.elapsed();
+        metrics::observe_lookup(provider.name(), elapsed);
+        (provider.name(), elapsed, outcome)
+    }))
+    .await;
+
+    let mut answers = Vec::with_capacity(outcomes.len());
+    let mut attempts = Vec::new();
+    let mut chosen: Option<(usize, LookupResult)> = None;
+
+    for (name, elapsed, outcome) in outcomes {
+        let latency_ms = elapsed.as_millis() as u64;
+        match outcome {
+            Ok(mut res) => {
+                answers.push(ProviderAnswer {
+                    provider: name.to_string(),
+                    ip: res.ip.clone(),
+                    asn: res.asn.clone(),
+                    latency_ms: Some(latency_ms),
+                    error: None,
+                });
+                if chosen.is_none() {
+                    res.source = Some(name.to_string());
+                    res.latency_ms = Some(latency_ms);
+                    res.checked_at = Some(checked_at);
+                    res.attempts = std::mem::take(&mut attempts);
+                    chosen = Some((answers.len() - 1, res));
+                }
+            }
+            Err(e) => {
+                warn!("IP lookup provider '{}' failed: {}", name, e);
+                metrics::lookup_failed(name, &e);
+                // Only failures before the chosen answer make it a fallback
+                if chosen.is_none() {
+                    attempts.push(LookupAttempt {
+                        provider: name.to_string(),
+                        error: e.clone(),
+                    });
+                }
+                answers.push(ProviderAnswer {
+                    provider: name.to_string(),
+                    ip: None,
+                    asn: None,
+                    latency_ms: Some(latency_ms),
+                    error: Some(e),
+                });
+            }
+        }
+    }
+
+    let Some((primary, mut res)) = chosen else {
+        return all_failed(checked_at, attempts);
+    };
+
+    let disagreements = disagreements(primary, &answers);
+    if disagreements.is_empty() {
+        debug!(
+            "IP lookup consensus: {} of {} providers agree",
+            answers.iter().filter(|a| a.error.is_none()).count(),
+            answers.len()
+        );
+    } else {
+        warn!("IP lookup providers disagree: {}", disagreements.join("; "));
+    }
+    res.consensus = Some(Consensus {
+        agreed: disagreements.is_empty(),
+        answers,
+        disagreements,
+    });
+    res
+}
+
+/// Differences between the chosen answer and every other successful one
+fn disagreements(primary: usize, answers: &[ProviderAnswer]) -> Vec<String> {
+    let chosen = &answers[primary];
+    let mut found = Vec::new();
+
+    for (index, other) in answers.iter().enumerate() {
+        if index == primary || other.error.is_some() {
+            continue;
+        }
+        if let (Some(ours), Some(theirs)) = (chosen.ip.as_deref(), other.ip.as_deref()) {
+            if !same_ip(ours, theirs) {
+                found.push(format!(
+                    "{} saw IP {}, {} saw {}",
+                    other.provider, theirs, chosen.provider, ours
+                ));
+            }
+        }
+        if let (Some(ours), Some(theirs)) = (chosen.asn.as_deref(), other.asn.as_deref()) {
+            if !ours.eq_ignore_ascii_case(theirs) {
+                found.push(format!(
+                    "{} saw ASN {}, {} saw {}",
+                    other.provider, theirs, chosen.provider, ours
+                ));
+            }
+        }
+    }
+    found
+}
+
+/// Compare addresses by value, so `::ffff:1.2.3.4` equals `1.2.3.4`
+fn same_ip(a: &str, b: &str) -> bool {
+    match (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        (Ok(a), Ok(b)) => a.to_canonical() == b.to_canonical(),
+        _ => a == b,
+    }
+}
+
+/// Result when no provider answered
+fn all_failed(
+    checked_at: chrono::DateTime<chrono::Utc>,
+    attempts: Vec<LookupAttempt>,
+) -> LookupResult {
+    let summary = attempts
+        .iter()
+        .map(|a| format!("{}: {}", a.provider, a.error))
+        .collect::<Vec<_>>()
+        .join("; ");
+    error!("All IP lookup services failed: {}", summary);
     LookupResult {
-        ip: None,
-        asn: None,
-        org: None,
-        country: None,
-        city: None,
-        region: None,
-        port_forwarded: None,
-        error: Some("ASN lookup failed".to_string()),
+        error: Some(if summary.is_empty() {
+            "ASN lookup failed".to_string()
+        } else {
+            format!("ASN lookup failed ({})", summary)
+        }),
+        checked_at: Some(checked_at),
+        attempts,
+        ..Default::default()
     }
 }
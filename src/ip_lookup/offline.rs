@@ -0,0 +1,287 @@
+//! Offline ASN Lookup
+//!
+//! Resolves an IP address to ASN, organization and country from a local
+//! database instead of a remote API, so it is not subject to rate limits and
+//! works without internet access.
+//!
+//! ## Database Formats
+//! - iptoasn.com TSV (`ip2asn-v4.tsv`, `ip2asn-combined.tsv`, `ip2asn-v4-u32.tsv`):
+//!   `range_start  range_end  AS_number  country_code  AS_description`
+//! - MaxMind GeoLite2-ASN (or another database with ASN fields, such as GeoIP2-ISP)
+//!   `.mmdb`, chosen by extension. City and Country databases have no ASN and are
+//!   rejected when loading.
+//!
+//! The `offline` provider still needs the public IP: it asks Gluetun's
+//! `/v1/publicip/ip` (or any URL answering the IP as JSON or plain text)
+//! and fills in the rest from the database.
+
+use super::{missing, IpLookupProvider};
+use crate::models::{LookupError, LookupResult};
+use async_trait::async_trait;
+use maxminddb::Reader;
+use reqwest::Client;
+use serde::Deserialize;
+use std::{
+    fmt, fs,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+    sync::Arc,
+};
+
+/// Local IP-to-ASN database
+pub struct AsnDatabase {
+    source: Source,
+}
+
+enum Source {
+    /// Non-overlapping ranges sorted by start address
+    Ranges(Vec<AsnRange>),
+    Mmdb(Reader<Vec<u8>>),
+}
+
+/// One iptoasn range, addresses as IPv6 (IPv4 mapped) integers
+struct AsnRange {
+    start: u128,
+    end: u128,
+    asn: u32,
+    country: Option<String>,
+    org: Option<String>,
+}
+
+/// What the database knows about an address
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsnRecord {
+    pub asn: String,
+    pub org: Option<String>,
+    pub country: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+}
+
+/// Fields read from GeoLite2-ASN records
+#[derive(Deserialize)]
+struct MmdbRecord {
+    autonomous_system_number: Option<u32>,
+    autonomous_system_organization: Option<String>,
+}
+
+impl AsnDatabase {
+    /// Load a database file, `.mmdb` as MaxMind and anything else as iptoasn TSV
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let is_mmdb = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mmdb"));
+
+        if is_mmdb {
+            let reader = Reader::open_readfile(path)
+                .map_err(|e| format!("invalid MaxMind database {}: {}", path.display(), e))?;
+            let database_type = &reader.metadata.database_type;
+            let upper = database_type.to_uppercase();
+            if !upper.contains("ASN") && !upper.contains("ISP") {
+                return Err(format!(
+                    "{} is a {} database without ASN fields, use GeoLite2-ASN or an iptoasn TSV",
+                    path.display(),
+                    database_type
+                ));
+            }
+            return Ok(Self {
+                source: Source::Mmdb(reader),
+            });
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Self::from_tsv(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse iptoasn TSV contents
+    ///
+    /// Unrouted ranges (`AS_number` 0) are skipped.
+    pub fn from_tsv(contents: &str) -> Result<Self, String> {
+        let mut ranges = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |what: &str| format!("line {}: {}", index + 1, what);
+
+            let mut fields = line.split('\t');
+            let (Some(start), Some(end), Some(asn)) = (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid("expected at least 3 tab-separated fields"));
+            };
+            let start = parse_address(start).ok_or_else(|| invalid("invalid range start"))?;
+            let end = parse_address(end).ok_or_else(|| invalid("invalid range end"))?;
+            let asn: u32 = asn
+                .trim()
+                .trim_start_matches("AS")
+                .parse()
+                .map_err(|_| invalid("invalid AS number"))?;
+            if asn == 0 {
+                continue;
+            }
+
+            let mut text = || {
+                fields
+                    .next()
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty() && *s != "None" && *s != "Unknown")
+                    .map(str::to_string)
+            };
+            let country = text();
+            let org = text();
+
+            ranges.push(AsnRange {
+                start,
+                end,
+                asn,
+                country,
+                org,
+            });
+        }
+
+        if ranges.is_empty() {
+            return Err("no routed ranges found".to_string());
+        }
+        ranges.sort_by_key(|r| r.start);
+
+        Ok(Self {
+            source: Source::Ranges(ranges),
+        })
+    }
+
+    /// Look up an address, `None` if the database does not cover it
+    pub fn resolve(&self, ip: IpAddr) -> Option<AsnRecord> {
+        match self.source {
+            Source::Ranges(ref ranges) => {
+                let key = to_u128(ip);
+                let index = ranges.partition_point(|r| r.start <= key).checked_sub(1)?;
+                let range = &ranges[index];
+                (key <= range.end).then(|| AsnRecord {
+                    asn: format!("AS{}", range.asn),
+                    org: range.org.clone(),
+                    country: range.country.clone(),
+                    region: None,
+                    city: None,
+                })
+            }
+            Source::Mmdb(ref reader) => {
+                let record: MmdbRecord = reader.lookup(ip).ok()?;
+                Some(AsnRecord {
+                    asn: format!("AS{}", record.autonomous_system_number?),
+                    org: record.autonomous_system_organization,
+                    country: None,
+                    region: None,
+                    city: None,
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AsnDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source {
+            Source::Ranges(ref ranges) => write!(f, "AsnDatabase(tsv, {} ranges)", ranges.len()),
+            Source::Mmdb(ref reader) => {
+                write!(f, "AsnDatabase(mmdb, {})", reader.metadata.database_type)
+            }
+        }
+    }
+}
+
+/// Parse an address column: dotted IPv4, IPv6, or an IPv4 integer (`-u32` files)
+fn parse_address(value: &str) -> Option<u128> {
+    let value = value.trim();
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| value.parse::<u32>().ok().map(|n| Ipv4Addr::from(n).into()))
+        .map(to_u128)
+}
+
+/// Address as an integer, IPv4 mapped into IPv6 so both families share one order
+fn to_u128(ip: IpAddr) -> u128 {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// Answer of the public IP endpoint
+#[derive(Deserialize)]
+struct PublicIpResponse {
+    #[serde(alias = "public_ip")]
+    ip: Option<String>,
+}
+
+/// Provider that fetches only the public IP and resolves it locally
+pub struct OfflineProvider {
+    ip_url: String,
+    api_key: Option<String>,
+    database: Arc<AsnDatabase>,
+}
+
+impl OfflineProvider {
+    /// `api_key` is sent as `X-API-Key`, for Gluetun's control server
+    pub fn new(ip_url: &str, api_key: Option<&str>, database: Arc<AsnDatabase>) -> Self {
+        Self {
+            ip_url: ip_url.to_string(),
+            api_key: api_key.map(str::to_string),
+            database,
+        }
+    }
+}
+
+#[async_trait]
+impl IpLookupProvider for OfflineProvider {
+    fn name(&self) -> &str {
+        "offline"
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<LookupResult, LookupError> {
+        let ip = fetch_public_ip(client, &self.ip_url, self.api_key.as_deref()).await?;
+        let record = self.database.resolve(ip).ok_or_else(|| missing("asn"))?;
+
+        Ok(LookupResult {
+            ip: Some(ip.to_string()),
+            asn: Some(record.asn),
+            org: record.org,
+            country: record.country,
+            city: record.city,
+            region: record.region,
+            ..Default::default()
+        })
+    }
+}
+
+/// Fetch the public IP, answered as JSON (`ip` or `public_ip`) or plain text
+pub async fn fetch_public_ip(
+    client: &Client,
+    url: &str,
+    api_key: Option<&str>,
+) -> Result<IpAddr, LookupError> {
+    let mut request = client.get(url);
+    if let Some(key) = api_key {
+        request = request.header("X-API-Key", key);
+    }
+
+    let resp = request.send().await?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(LookupError::HttpStatus {
+            status: status.as_u16(),
+        });
+    }
+
+    let body = resp.text().await?;
+    let ip = match serde_json::from_str::<PublicIpResponse>(&body) {
+        Ok(data) => data.ip.ok_or_else(|| missing("ip"))?,
+        Err(_) => body.trim().to_string(),
+    };
+    ip.parse().map_err(|_| LookupError::Decode {
+        message: format!("'{}' is not an IP address", ip),
+    })
+}
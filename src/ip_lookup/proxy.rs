@@ -0,0 +1,110 @@
+//! Proxy Lookup
+//!
+//! Sends the provider chain through Gluetun's HTTP proxy or a SOCKS5 proxy
+//! (`IP_LOOKUP_PROXY`), so the exit IP/ASN is the one seen by clients that use
+//! the proxy instead of sharing Gluetun's network namespace.
+//! - Requests to the Gluetun control server bypass the proxy
+//! - Proxy reachability is checked on its own (TCP connect), so a broken proxy
+//!   is told apart from a broken tunnel
+
+use crate::{config::redact_url, models::ProxyStatus};
+use reqwest::{Client, NoProxy, Proxy, Url};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tracing::warn;
+
+/// How long connecting to the proxy may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Proxied HTTP client and the proxy address
+pub struct LookupProxy {
+    /// Proxy URL without credentials, for display
+    url: String,
+    host: String,
+    port: u16,
+    client: Client,
+}
+
+impl LookupProxy {
+    /// Build a client sending every request through `url`, except to `bypass` (the Gluetun API)
+    pub fn new(url: &str, timeout: Duration, bypass: Option<&str>) -> Result<Self, String> {
+        let (host, port) = parse_proxy_url(url)?;
+
+        let no_proxy = bypass
+            .and_then(|bypass| Url::parse(bypass).ok())
+            .and_then(|bypass| bypass.host_str().and_then(NoProxy::from_string));
+        let proxy = Proxy::all(url)
+            .map_err(|e| format!("invalid proxy URL: {}", e))?
+            .no_proxy(no_proxy);
+        let client = Client::builder()
+            .timeout(timeout)
+            .proxy(proxy)
+            .build()
+            .map_err(|e| format!("failed to create HTTP client: {}", e))?;
+
+        Ok(Self {
+            url: redact_url(url),
+            host,
+            port,
+            client,
+        })
+    }
+
+    /// Client for the provider chain
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Whether the proxy accepts connections
+    pub async fn check(&self) -> ProxyStatus {
+        let started = Instant::now();
+        let connect = TcpStream::connect((self.host.as_str(), self.port));
+        let error = match tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!(
+                "connect timed out after {}s",
+                CONNECT_TIMEOUT.as_secs()
+            )),
+        };
+        if let Some(ref e) = error {
+            warn!("Lookup proxy {} unreachable: {}", self.url, e);
+        }
+
+        ProxyStatus {
+            url: self.url.clone(),
+            reachable: error.is_none(),
+            latency_ms: error
+                .is_none()
+                .then(|| started.elapsed().as_millis() as u64),
+            error,
+        }
+    }
+}
+
+/// Host and port of an `http://`, `https://`, `socks5://` or `socks5h://` proxy URL
+///
+/// The port defaults to 80/443 for HTTP and 1080 for SOCKS5.
+pub fn parse_proxy_url(url: &str) -> Result<(String, u16), String> {
+    let invalid = || {
+        format!(
+            "'{}' is not an http://, https://, socks5:// or socks5h:// URL (Shadowsocks is not supported, use a local client's SOCKS5 port)",
+            redact_url(url)
+        )
+    };
+    let parsed = Url::parse(url).map_err(|_| invalid())?;
+    let default_port = match parsed.scheme() {
+        "http" => 80,
+        "https" => 443,
+        "socks5" | "socks5h" => 1080,
+        _ => return Err(invalid()),
+    };
+    let host = parsed
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .ok_or_else(invalid)?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    Ok((host, parsed.port().unwrap_or(default_port)))
+}
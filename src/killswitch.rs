@@ -0,0 +1,304 @@
+// Kill Switch Module
+//!
+//! Proves that Gluetun's firewall keeps traffic inside the tunnel, rather than
+//! only that the current exit looks right.
+//!
+//! ## Baseline
+//! Every live lookup also asks Gluetun for its public IP (`/v1/publicip/ip` only) and
+//! compares it with the home (non-VPN) IP: `HOME_IPS`, and the IP answered by
+//! `HOME_LOOKUP_URL`, a lookup service reachable outside the tunnel (e.g. on the LAN).
+//! An exit IP equal to a home IP means traffic bypasses the VPN.
+//!
+//! ## Probe
+//! Every `KILL_SWITCH_PROBE_INTERVAL_HOURS` the tunnel is stopped through the
+//! control server (`PUT /v1/vpn/status`), `KILL_SWITCH_PROBE_URL` is requested once
+//! Gluetun reports it stopped, and the tunnel is started again. The request must fail
+//! to connect: an answer means the firewall let traffic out without the VPN, any other
+//! failure (DNS, TLS, a bad URL) proves nothing and is reported as an error. Only meaningful when the monitor shares
+//! Gluetun's network namespace. While it runs, [`KillSwitch::probing`] is true so
+//! the stopped tunnel is not reported as an outage.
+
+use crate::{
+    ip_lookup::{fetch_gluetun_exit_ip, fetch_public_ip, fetch_vpn_status, set_vpn_status},
+    models::{KillSwitchProbe, KillSwitchStatus, ProbeOutcome, TunnelState},
+};
+use ipnet::IpNet;
+use reqwest::Client;
+use std::{
+    error::Error as _,
+    io,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+};
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Default `KILL_SWITCH_PROBE_URL`, an IP address so a blocked resolver cannot hide a leak
+pub const DEFAULT_PROBE_URL: &str = "https://1.1.1.1/cdn-cgi/trace";
+
+/// How long the probe request may take with the tunnel stopped
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for Gluetun to report the tunnel stopped before probing
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for Gluetun to report a public IP after the restart
+const RESTART_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Compares Gluetun's exit with the home IP and runs the tunnel-stop probe
+#[derive(Debug)]
+pub struct KillSwitch {
+    gluetun_url: String,
+    api_key: Option<String>,
+    home_ips: Vec<IpNet>,
+    home_lookup_url: Option<String>,
+    probe_url: String,
+    last_probe: RwLock<Option<KillSwitchProbe>>,
+    /// Set from stopping the tunnel until it reconnected
+    probing: AtomicBool,
+}
+
+impl KillSwitch {
+    pub fn new(
+        gluetun_url: &str,
+        api_key: Option<&str>,
+        home_ips: Vec<IpNet>,
+        home_lookup_url: Option<String>,
+    ) -> Self {
+        Self {
+            gluetun_url: gluetun_url.trim_end_matches('/').to_string(),
+            api_key: api_key.map(str::to_string),
+            home_ips,
+            home_lookup_url,
+            probe_url: DEFAULT_PROBE_URL.to_string(),
+            last_probe: RwLock::new(None),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    /// Request `url` instead of the default while the tunnel is stopped
+    pub fn with_probe_url(mut self, url: &str) -> Self {
+        self.probe_url = url.to_string();
+        self
+    }
+
+    /// Compare Gluetun's exit IP with the home IPs
+    pub async fn verify(&self, client: &Client) -> KillSwitchStatus {
+        let (exit, direct) = tokio::join!(
+            fetch_gluetun_exit_ip(client, &self.gluetun_url),
+            self.fetch_home_ip(client)
+        );
+
+        let mut home = self.home_ips.clone();
+        let mut errors = Vec::new();
+        match direct {
+            Some(Ok(ip)) => home.push(IpNet::from(ip.to_canonical())),
+            Some(Err(e)) => errors.push(e),
+            None => {}
+        }
+
+        let exit_ip = match exit {
+            Ok(ip) => Some(ip),
+            Err(e) => {
+                errors.push(format!("Gluetun exit lookup failed: {}", e));
+                None
+            }
+        };
+        let leaking = exit_ip
+            .as_deref()
+            .and_then(|ip| ip.parse::<IpAddr>().ok())
+            .is_some_and(|addr| {
+                let addr = addr.to_canonical();
+                home.iter().any(|net| net.contains(&addr))
+            });
+        if leaking {
+            error!(
+                "Gluetun exit IP {} is a home IP, traffic bypasses the tunnel",
+                exit_ip.as_deref().unwrap_or_default()
+            );
+        }
+
+        KillSwitchStatus {
+            exit_ip,
+            home_count: home.len(),
+            leaking,
+            error: (!errors.is_empty()).then(|| errors.join("; ")),
+            probe: self.last_probe(),
+        }
+    }
+
+    /// Stop the tunnel, check that the probe URL is unreachable and start it again
+    pub async fn probe(&self) -> KillSwitchProbe {
+        self.probing.store(true, Ordering::Relaxed);
+        let probe = self.stop_and_probe().await;
+        self.probing.store(false, Ordering::Relaxed);
+        probe
+    }
+
+    /// True while a probe has the tunnel stopped or is waiting for it to reconnect
+    pub fn probing(&self) -> bool {
+        self.probing.load(Ordering::Relaxed)
+    }
+
+    async fn stop_and_probe(&self) -> KillSwitchProbe {
+        // A fresh client without pooled connections that could outlive the tunnel
+        let client = Client::builder()
+            .timeout(PROBE_TIMEOUT)
+            .pool_max_idle_per_host(0)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        if let Err(e) = self.set_status(&client, "stopped").await {
+            return self.record(
+                ProbeOutcome::Error,
+                format!("failed to stop the VPN: {}", e),
+            );
+        }
+        if let Err(e) = self.wait_stopped(&client).await {
+            let message = match self.restart(&client).await {
+                Ok(()) => e,
+                Err(restart) => format!("{}; {}", e, restart),
+            };
+            return self.record(ProbeOutcome::Error, message);
+        }
+
+        let (outcome, message) = match client.get(&self.probe_url).send().await {
+            Ok(resp) => (
+                ProbeOutcome::Leaked,
+                format!(
+                    "{} answered HTTP {} with the tunnel stopped",
+                    self.probe_url,
+                    resp.status().as_u16()
+                ),
+            ),
+            Err(e) if blocked(&e) => (
+                ProbeOutcome::Held,
+                format!("{} unreachable with the tunnel stopped", self.probe_url),
+            ),
+            Err(e) => (
+                ProbeOutcome::Error,
+                format!(
+                    "{} failed, but not because the connection was blocked: {}",
+                    self.probe_url, e
+                ),
+            ),
+        };
+
+        // A VPN left down is worse than a held kill switch is good
+        let (outcome, message) = match self.restart(&client).await {
+            Ok(()) => (outcome, message),
+            Err(e) if outcome == ProbeOutcome::Leaked => (outcome, format!("{}; {}", message, e)),
+            Err(e) => (ProbeOutcome::Error, format!("{}; {}", message, e)),
+        };
+        self.record(outcome, message)
+    }
+
+    /// Most recent probe result
+    pub fn last_probe(&self) -> Option<KillSwitchProbe> {
+        self.last_probe
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    async fn fetch_home_ip(&self, client: &Client) -> Option<Result<IpAddr, String>> {
+        let url = self.home_lookup_url.as_deref()?;
+        Some(
+            fetch_public_ip(client, url, None)
+                .await
+                .map_err(|e| format!("home lookup failed: {}", e)),
+        )
+    }
+
+    async fn set_status(&self, client: &Client, status: &str) -> Result<(), String> {
+        set_vpn_status(client, &self.gluetun_url, self.api_key.as_deref(), status)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Wait until Gluetun reports the tunnel as stopped
+    async fn wait_stopped(&self, client: &Client) -> Result<(), String> {
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        loop {
+            let state = fetch_vpn_status(client, &self.gluetun_url, self.api_key.as_deref()).await;
+            if matches!(state, Ok(TunnelState::Stopped)) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "the VPN did not stop within {}s",
+                    STOP_TIMEOUT.as_secs()
+                ));
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Start the tunnel and wait until Gluetun reports a public IP again
+    async fn restart(&self, client: &Client) -> Result<(), String> {
+        self.set_status(client, "running")
+            .await
+            .map_err(|e| format!("failed to restart the VPN: {}", e))?;
+
+        let deadline = Instant::now() + RESTART_TIMEOUT;
+        loop {
+            if fetch_gluetun_exit_ip(client, &self.gluetun_url)
+                .await
+                .is_ok()
+            {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "the VPN did not reconnect within {}s",
+                    RESTART_TIMEOUT.as_secs()
+                ));
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    fn record(&self, outcome: ProbeOutcome, message: String) -> KillSwitchProbe {
+        match outcome {
+            ProbeOutcome::Held => info!("Kill switch probe: {}", message),
+            ProbeOutcome::Leaked => error!("Kill switch probe: {}", message),
+            ProbeOutcome::Error => warn!("Kill switch probe: {}", message),
+        }
+        let probe = KillSwitchProbe {
+            checked_at: chrono::Utc::now(),
+            outcome,
+            message,
+        };
+        *self.last_probe.write().unwrap_or_else(|e| e.into_inner()) = Some(probe.clone());
+        probe
+    }
+}
+
+/// True when the request timed out or the connection was refused or dropped, as
+/// a firewall does, rather than failing before it reached the network
+fn blocked(error: &reqwest::Error) -> bool {
+    if error.is_timeout() {
+        return true;
+    }
+    let mut source = error.source();
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<io::Error>() {
+            return matches!(
+                io.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::HostUnreachable
+                    | io::ErrorKind::NetworkUnreachable
+                    // Gluetun's OUTPUT chain drops the packet locally
+                    | io::ErrorKind::PermissionDenied
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
@@ -1,7 +1,16 @@
 // Lib exports for testing
+pub mod allowlist;
+pub mod cli;
 pub mod config;
+pub mod country;
+pub mod discover;
+pub mod dns;
+pub mod geofence;
 pub mod handlers;
 pub mod ip_lookup;
+pub mod killswitch;
+pub mod metrics;
 pub mod models;
 pub mod monitoring;
 pub mod notification;
+pub mod policy;
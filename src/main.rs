@@ -6,19 +6,38 @@
 //!
 //! ## Responsibilities
 //! - Initialize tracing/logging
-//! - Load configuration from environment variables
+//! - Parse command-line arguments
+//! - Load configuration from the config file and environment variables
+//! - Run the `discover-asns` command instead of the server if requested
+//! - Validate configuration, exiting on errors (or after reporting with `--check-config`)
 //! - Create HTTP client and application state
 //! - Spawn periodic notification task
 //! - Spawn VPN change detection task
-//! - Start Axum HTTP server on port 3010
+//! - Spawn the kill switch probe, if enabled
+//! - Spawn config reloader (SIGHUP / config file changes)
+//! - Start Axum HTTP server on `MONITOR_BIND_ADDR:MONITOR_PORT` (default `0.0.0.0:3010`)
+//!   and optionally on a Unix domain socket (`/status`, `/check`, `/metrics`, `/config`,
+//!   `/asns/observed`, `/admin/asns`)
 
-use gluetun_monitor::{config, handlers, models, monitoring};
+use gluetun_monitor::{
+    allowlist::{AllowList, AllowlistOverrides},
+    cli, config, discover, dns, handlers, ip_lookup, killswitch, models, monitoring, policy,
+};
 
-use axum::{routing::get, Router};
+use axum::{
+    routing::{get, post},
+    Router,
+};
 use models::AppState;
-use std::sync::Arc;
+use std::{
+    os::unix::fs::FileTypeExt,
+    sync::{Arc, RwLock},
+};
 use tokio::time::Duration;
-use tracing::info;
+use tracing::{debug, error, info, warn};
+
+/// Timeout of the HTTP clients used for lookups and the Gluetun API
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() {
@@ -30,8 +49,89 @@ async fn main() {
         )
         .init();
 
+    // Parse command-line arguments
+    let args = cli::Cli::parse().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, cli::USAGE);
+        std::process::exit(2);
+    });
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
     // Load configuration
-    let config = config::Config::from_env();
+    let config = match config::Config::load(args.config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(ref path) = args.config_path {
+        info!("Loaded config file: {}", path.display());
+    }
+
+    // Discovery only needs the Gluetun API, the allowlist is what it produces
+    if let cli::Command::DiscoverAsns {
+        iterations,
+        timeout_secs,
+    } = args.command
+    {
+        let Some(ref gluetun_url) = config.gluetun_url else {
+            eprintln!("discover-asns requires GLUETUN_API_URL to be set");
+            std::process::exit(1);
+        };
+        let options = discover::DiscoverOptions {
+            iterations,
+            wait_timeout: Duration::from_secs(timeout_secs),
+            poll_interval: Duration::from_secs(5),
+        };
+        let client = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .expect("Failed to create HTTP client");
+
+        let asns = discover::run(
+            &client,
+            gluetun_url.trim_end_matches('/'),
+            config.gluetun_api_key.as_deref(),
+            &options,
+        )
+        .await;
+        if asns.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Validate configuration
+    let (report, asn_database) = config.validate_with_database(None);
+    if args.check_config {
+        for warning in &report.warnings {
+            println!("warning: {}", warning);
+        }
+        for err in &report.errors {
+            eprintln!("error: {}", err);
+        }
+        if !report.is_ok() {
+            eprintln!("Configuration invalid: {} error(s)", report.errors.len());
+            std::process::exit(1);
+        }
+        println!("Configuration OK");
+        return;
+    }
+    for warning in &report.warnings {
+        warn!("Config: {}", warning);
+    }
+    if !report.is_ok() {
+        for err in &report.errors {
+            error!("Config: {}", err);
+        }
+        error!("Invalid configuration, run with --check-config for details");
+        std::process::exit(1);
+    }
+    let effective_config = config.redacted();
+    debug!("Effective configuration: {}", effective_config);
 
     // Log Gluetun configuration
     if let Some(ref url) = config.gluetun_url {
@@ -41,19 +141,199 @@ async fn main() {
         }
     }
 
+    // The local ASN database was loaded while validating
+    if let Some(ref path) = config.asn_database {
+        info!("ASN database loaded from {}", path.display());
+    }
+
+    // Build IP lookup provider chain
+    let providers = ip_lookup::build_providers(
+        &config.ip_lookup_providers,
+        config.gluetun_url.as_deref(),
+        config.gluetun_api_key.as_deref(),
+        &config.provider_timeouts,
+        asn_database.as_ref(),
+    )
+    .unwrap_or_else(|e| {
+        error!("Invalid IP_LOOKUP_PROVIDERS: {}", e);
+        std::process::exit(1)
+    });
+    info!(
+        "IP lookup providers ({}): {}",
+        config.lookup_mode,
+        config.ip_lookup_providers.join(", ")
+    );
+
+    // Pin the chain to IPv4 and look up the IPv6 exit separately
+    let dual_stack = if config.dual_stack {
+        let ipv6_providers = ip_lookup::build_ipv6_providers(
+            &config.ipv6_lookup_providers,
+            &config.provider_timeouts,
+            asn_database.as_ref(),
+        )
+        .unwrap_or_else(|e| {
+            error!("Invalid IPV6_LOOKUP_PROVIDERS: {}", e);
+            std::process::exit(1)
+        });
+        info!(
+            "Dual-stack lookups enabled, IPv6 providers: {}",
+            config.ipv6_lookup_providers.join(", ")
+        );
+        Some(
+            ip_lookup::DualStack::new(HTTP_TIMEOUT, ipv6_providers)
+                .expect("Failed to create HTTP client"),
+        )
+    } else {
+        None
+    };
+
+    // Send the chain through Gluetun's HTTP proxy or a SOCKS5 proxy
+    let lookup_proxy = config.lookup_proxy.as_deref().map(|url| {
+        let proxy = ip_lookup::LookupProxy::new(url, HTTP_TIMEOUT, config.gluetun_url.as_deref())
+            .unwrap_or_else(|e| {
+                error!("Invalid IP_LOOKUP_PROXY: {}", e);
+                std::process::exit(1)
+            });
+        info!("IP lookups go through proxy {}", config::redact_url(url));
+        proxy
+    });
+
+    // Probe which resolvers answer DNS queries
+    let dns_probe = config.dns_leak_check.then(|| {
+        info!(
+            "DNS leak probe enabled via {} ({})",
+            config.dns_whoami,
+            config
+                .dns_server
+                .map_or("system resolver".to_string(), |server| server.to_string())
+        );
+        dns::DnsProbe::new(
+            &config.dns_whoami,
+            config.dns_probe_names.clone(),
+            config.dns_server,
+            asn_database.clone(),
+        )
+    });
+
+    // Compare Gluetun's exit with the home IP, the exit IP comes from Gluetun
+    let kill_switch = match config.gluetun_url {
+        Some(ref gluetun_url) if config.kill_switch_enabled() => {
+            info!(
+                "Kill switch verification enabled: {} home IP(s){}, probe {}",
+                config.home_ips.len(),
+                if config.home_lookup_url.is_some() {
+                    " and HOME_LOOKUP_URL"
+                } else {
+                    ""
+                },
+                match config.kill_switch_probe_interval_hours {
+                    0 => "disabled".to_string(),
+                    hours => format!("every {} hours", hours),
+                }
+            );
+            Some(
+                killswitch::KillSwitch::new(
+                    gluetun_url,
+                    config.gluetun_api_key.as_deref(),
+                    config.home_ips.clone(),
+                    config.home_lookup_url.clone(),
+                )
+                .with_probe_url(&config.kill_switch_probe_url),
+            )
+        }
+        _ => None,
+    };
+
+    // Build the /check policy
+    let mut policy = policy::Policy::from_config(&config).unwrap_or_else(|e| {
+        error!("Invalid CHECK_RULES: {}", e);
+        std::process::exit(1)
+    });
+    policy.asn_database = asn_database;
+    info!(
+        "Check rules: {}",
+        policy
+            .rules
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    // Load allowlist changes made through the admin API
+    let overrides = match config.admin_state_file {
+        Some(ref path) => match AllowlistOverrides::load(path) {
+            Ok(overrides) => overrides,
+            Err(e) => {
+                error!("Failed to load admin state: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => AllowlistOverrides::default(),
+    };
+    if !overrides.is_empty() {
+        info!(
+            "Admin allowlist overrides: {} added, {} removed",
+            overrides.added.len(),
+            overrides.removed.len()
+        );
+    }
+    if config.learn_window_hours > 0 {
+        info!(
+            "Learn mode enabled, recording ASNs seen in the last {} hours on /asns/observed",
+            config.learn_window_hours
+        );
+    }
+    if config.admin_token.is_some() {
+        info!("Admin API enabled on /admin/asns");
+    }
+
+    let listen_addr = config.listen_addr();
+    let unix_socket = config.unix_socket.clone();
+
     // Create HTTP client
     let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(30))
+        .timeout(HTTP_TIMEOUT)
         .build()
         .expect("Failed to create HTTP client");
 
+    let mut lookup_cache =
+        ip_lookup::LookupCache::new(Duration::from_secs(config.lookup_cache_ttl_seconds))
+            .with_mode(config.lookup_mode);
+    if let Some(dual_stack) = dual_stack {
+        lookup_cache = lookup_cache.with_dual_stack(dual_stack);
+    }
+    if let Some(lookup_proxy) = lookup_proxy {
+        lookup_cache = lookup_cache.with_proxy(lookup_proxy);
+    }
+    if let Some(dns_probe) = dns_probe {
+        lookup_cache = lookup_cache.with_dns_probe(dns_probe);
+    }
+    if let Some(kill_switch) = kill_switch {
+        lookup_cache = lookup_cache.with_kill_switch(kill_switch);
+    }
+    if let Some(ref gluetun_url) = config.gluetun_url {
+        lookup_cache = lookup_cache.with_tunnel_status(ip_lookup::GluetunProvider::new(
+            gluetun_url,
+            config.gluetun_api_key.as_deref(),
+        ));
+    }
+
     // Create application state
     let state = AppState {
-        allowed_asns: Arc::new(config.allowed_asns),
+        allowed_asns: Arc::new(AllowList::with_overrides(config.allowed_asns, overrides)),
+        policy: Arc::new(policy),
         client,
-        ntfy_url: config.ntfy_url,
+        ntfy_urls: Arc::new(config.ntfy_urls),
         gluetun_url: config.gluetun_url,
         gluetun_api_key: config.gluetun_api_key,
+        providers: Arc::new(providers),
+        lookup_cache: Arc::new(lookup_cache),
+        effective_config: Arc::new(RwLock::new(effective_config)),
+        admin_token: config.admin_token,
+        admin_state_file: config.admin_state_file,
+        asn_observer: (config.learn_window_hours > 0)
+            .then(|| Arc::new(monitoring::AsnObserver::new(config.learn_window_hours))),
     };
 
     // Spawn periodic notifier in background
@@ -70,16 +350,88 @@ async fn main() {
         monitoring::start_change_detector(detector_state, check_interval).await;
     });
 
+    // Spawn kill switch probe in background
+    if config.kill_switch_probe_interval_hours > 0 {
+        let probe_state = state.clone();
+        let probe_interval = config.kill_switch_probe_interval_hours;
+        tokio::spawn(async move {
+            monitoring::start_kill_switch_probe(probe_state, probe_interval).await;
+        });
+    }
+
+    // Spawn config reloader in background
+    let reloader_state = state.clone();
+    let config_path = args.config_path.clone();
+    tokio::spawn(async move {
+        monitoring::start_config_reloader(reloader_state, config_path).await;
+    });
+
     // Create router
     let app = Router::new()
         .route("/status", get(handlers::status_handler))
         .route("/check", get(handlers::check_handler))
+        .route("/metrics", get(handlers::metrics_handler))
+        .route("/config", get(handlers::config_handler))
+        .route(
+            "/admin/asns",
+            get(handlers::admin_list_handler)
+                .post(handlers::admin_add_handler)
+                .delete(handlers::admin_remove_handler),
+        )
+        .route("/admin/asns/promote", post(handlers::admin_promote_handler))
+        .route("/asns/observed", get(handlers::observed_asns_handler))
         .with_state(state);
 
-    // Start server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3010")
-        .await
-        .expect("Failed to bind to port 3010");
+    // Start Unix socket server if configured
+    if let Some(path) = unix_socket {
+        // Remove a stale socket left behind by a previous run, and nothing else
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    error!("Failed to remove stale socket {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+            Ok(_) => {
+                error!(
+                    "Invalid MONITOR_UNIX_SOCKET: {} exists and is not a socket",
+                    path.display()
+                );
+                std::process::exit(1);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                error!("Failed to inspect {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+
+        let unix_listener = match tokio::net::UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to bind Unix socket {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+
+        info!("Listening on unix:{}", path.display());
+
+        let unix_app = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(unix_listener, unix_app).await {
+                error!("Unix socket server failed: {}", e);
+            }
+        });
+    }
+
+    // Start TCP server
+    let listener = match tokio::net::TcpListener::bind(listen_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind to {}: {}", listen_addr, e);
+            std::process::exit(1);
+        }
+    };
 
     info!("Listening on {}", listener.local_addr().unwrap());
 
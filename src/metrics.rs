@@ -0,0 +1,179 @@
+// Metrics Module
+//!
+//! Prometheus metrics exposed on the `/metrics` endpoint.
+//!
+//! Counters and histograms are updated where the events happen (lookups,
+//! notifications, change detection). Status gauges are refreshed from the
+//! current lookup result on each scrape.
+//!
+//! ## Metrics
+//! - `gluetun_monitor_vpn_healthy`: 1 if `/check` would pass, 0 otherwise
+//! - `gluetun_monitor_asn_allowed`: 1 if the current ASN is in the allowlist
+//! - `gluetun_monitor_home_isp_leak`: 1 if the current ASN is in the home ISP deny-list
+//! - `gluetun_monitor_port_forwarded`: Forwarded port, 0 if none
+//! - `gluetun_monitor_lookup_duration_seconds{provider}`: Provider lookup latency
+//! - `gluetun_monitor_lookup_failures_total{provider,kind}`: Failed provider lookups
+//! - `gluetun_monitor_notification_attempts_total`: ntfy send attempts (including retries)
+//! - `gluetun_monitor_notification_failures_total`: Failed ntfy send attempts
+//! - `gluetun_monitor_changes_total{field}`: Detected VPN changes by field
+
+use crate::{
+    allowlist::AsnVerdict,
+    models::{LookupError, LookupResult},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::{sync::LazyLock, time::Duration};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+static VPN_HEALTHY: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "gluetun_monitor_vpn_healthy",
+        "Whether the VPN passes the /check health check (1) or not (0)",
+    ))
+});
+
+static ASN_ALLOWED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "gluetun_monitor_asn_allowed",
+        "Whether the current ASN is in the allowlist (1) or not (0)",
+    ))
+});
+
+static HOME_ISP_LEAK: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "gluetun_monitor_home_isp_leak",
+        "Whether the current ASN is a denied home ISP ASN (1) or not (0)",
+    ))
+});
+
+static PORT_FORWARDED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "gluetun_monitor_port_forwarded",
+        "Port forwarded by Gluetun, 0 if none",
+    ))
+});
+
+static LOOKUP_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "gluetun_monitor_lookup_duration_seconds",
+            "Duration of IP lookups per provider",
+        )
+        .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+        &["provider"],
+    ))
+});
+
+static LOOKUP_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "gluetun_monitor_lookup_failures_total",
+            "Failed IP lookups per provider and error kind",
+        ),
+        &["provider", "kind"],
+    ))
+});
+
+static NOTIFICATION_ATTEMPTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new(
+        "gluetun_monitor_notification_attempts_total",
+        "Notification send attempts, including retries",
+    ))
+});
+
+static NOTIFICATION_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new(
+        "gluetun_monitor_notification_failures_total",
+        "Failed notification send attempts",
+    ))
+});
+
+static CHANGES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "gluetun_monitor_changes_total",
+            "Detected VPN changes by field",
+        ),
+        &["field"],
+    ))
+});
+
+/// Register a collector with the monitor registry
+fn register<C>(collector: prometheus::Result<C>) -> C
+where
+    C: prometheus::core::Collector + Clone + 'static,
+{
+    let collector = collector.expect("Invalid metric definition");
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("Failed to register metric");
+    collector
+}
+
+/// Record how long a provider took to answer (or fail)
+pub fn observe_lookup(provider: &str, duration: Duration) {
+    LOOKUP_DURATION
+        .with_label_values(&[provider])
+        .observe(duration.as_secs_f64());
+}
+
+/// Record a failed provider lookup
+pub fn lookup_failed(provider: &str, error: &LookupError) {
+    let kind = match error {
+        LookupError::Timeout => "timeout",
+        LookupError::Request { .. } => "request",
+        LookupError::HttpStatus { .. } => "http_status",
+        LookupError::Decode { .. } => "decode",
+        LookupError::MissingField { .. } => "missing_field",
+    };
+    LOOKUP_FAILURES.with_label_values(&[provider, kind]).inc();
+}
+
+/// Record a notification send attempt and whether it succeeded
+pub fn notification_attempt(success: bool) {
+    NOTIFICATION_ATTEMPTS.inc();
+    if !success {
+        NOTIFICATION_FAILURES.inc();
+    }
+}
+
+/// Record a detected change of the given field (`ip`, `country`, `asn`, `dns`)
+pub fn change_detected(field: &str) {
+    CHANGES.with_label_values(&[field]).inc();
+}
+
+/// Update the status gauges from the current lookup result
+///
+/// `healthy` is whether the `/check` policy passed.
+pub fn record_status(info: &LookupResult, verdict: AsnVerdict, healthy: bool) {
+    let asn_allowed = verdict == AsnVerdict::Allowed;
+
+    VPN_HEALTHY.set(healthy as i64);
+    ASN_ALLOWED.set(asn_allowed as i64);
+    HOME_ISP_LEAK.set((verdict == AsnVerdict::Denied) as i64);
+    PORT_FORWARDED.set(info.port_forwarded.map(i64::from).unwrap_or(0));
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render() -> String {
+    // Touch every metric so it is exported even before its first event
+    LazyLock::force(&VPN_HEALTHY);
+    LazyLock::force(&ASN_ALLOWED);
+    LazyLock::force(&HOME_ISP_LEAK);
+    LazyLock::force(&PORT_FORWARDED);
+    LazyLock::force(&LOOKUP_DURATION);
+    LazyLock::force(&LOOKUP_FAILURES);
+    LazyLock::force(&NOTIFICATION_ATTEMPTS);
+    LazyLock::force(&NOTIFICATION_FAILURES);
+    LazyLock::force(&CHANGES);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("Failed to encode metrics");
+    String::from_utf8(buffer).expect("Metrics are not valid UTF-8")
+}
@@ -4,15 +4,41 @@
 //!
 //! ## Key Types
 //! - `LookupResult`: IP lookup response with ASN, location, and port forwarding info
+//! - `LookupError` / `LookupAttempt`: Typed failure of a single lookup provider
+//! - `Consensus` / `ProviderAnswer`: Answers of every provider in consensus mode
+//! - `Ipv6Lookup`: Separate IPv6 exit lookup in dual-stack mode
+//! - `DnsReport`: Resolvers and probe names seen by the DNS leak probe
+//! - `KillSwitchStatus` / `KillSwitchProbe`: Exit IP compared with the home IP, and the
+//!   last tunnel-stop probe
+//! - `ProxyStatus`: Reachability of the proxy the lookups go through
+//! - `TunnelStatus` / `TunnelState`: VPN tunnel state reported by Gluetun's control server
 //! - `StatusResponse`: Response for `/status` endpoint (informational)
 //! - `CheckResponse`: Response for `/check` endpoint (health check)
+//! - `LookupQuery`: Query parameters accepted by `/status` and `/check`
+//! - `AsnRequest` / `AdminAsnsResponse`: Body and response of the `/admin/asns` API
+//! - `PromoteRequest`: Body of `/admin/asns/promote`
+//! - `ObservedAsnsResponse`: Response for `/asns/observed` (learn mode)
 //! - `AppState`: Shared application state passed to all handlers
 
+use crate::{
+    allowlist::AllowList,
+    ip_lookup::{IpLookupProvider, LookupCache},
+    monitoring::{AsnObserver, ObservedAsn},
+    policy::{Policy, RuleResult, RuleStatus},
+};
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
 
 /// Result from IP lookup services
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LookupResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ip: Option<String>,
@@ -30,6 +56,332 @@ pub struct LookupResult {
     pub port_forwarded: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Name of the provider that answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Time taken by the answering provider, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// When the lookup was performed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Age of the cached snapshot in seconds, absent for live lookups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_age_secs: Option<u64>,
+    /// Providers that failed before this result was obtained
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<LookupAttempt>,
+    /// Answers of every provider, in consensus mode only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consensus: Option<Consensus>,
+    /// Separate IPv6 exit lookup, in dual-stack mode only (`ip` is then the IPv4 exit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<Ipv6Lookup>,
+    /// DNS leak probe, when enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsReport>,
+    /// Gluetun's exit IP compared with the home IP, when kill switch verification is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kill_switch: Option<KillSwitchStatus>,
+    /// Proxy the lookups went through, checked separately from the lookup itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyStatus>,
+    /// Tunnel state from Gluetun's control server, queried live even for cached results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel: Option<TunnelStatus>,
+}
+
+impl LookupResult {
+    /// True when the answer came from a provider further down the chain
+    /// because an earlier one failed
+    pub fn is_fallback(&self) -> bool {
+        self.error.is_none() && !self.attempts.is_empty()
+    }
+
+    /// True when consensus mode found providers disagreeing on IP or ASN
+    pub fn is_degraded(&self) -> bool {
+        self.consensus.as_ref().is_some_and(|c| !c.agreed)
+    }
+}
+
+/// Why a single lookup provider failed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LookupError {
+    /// Request did not complete within the client timeout
+    Timeout,
+    /// Connection or other transport-level failure
+    Request { message: String },
+    /// Provider answered with a non-success HTTP status
+    HttpStatus { status: u16 },
+    /// Response body was not the expected JSON
+    Decode { message: String },
+    /// Response was valid but lacked a required field
+    MissingField { field: String },
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::Timeout => write!(f, "timed out"),
+            LookupError::Request { message } => write!(f, "request failed: {}", message),
+            LookupError::HttpStatus { status } => write!(f, "HTTP status {}", status),
+            LookupError::Decode { message } => write!(f, "invalid response: {}", message),
+            LookupError::MissingField { field } => write!(f, "missing field '{}'", field),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+impl From<reqwest::Error> for LookupError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            LookupError::Timeout
+        } else if e.is_decode() {
+            LookupError::Decode {
+                message: e.to_string(),
+            }
+        } else if let Some(status) = e.status() {
+            LookupError::HttpStatus {
+                status: status.as_u16(),
+            }
+        } else {
+            LookupError::Request {
+                message: e.to_string(),
+            }
+        }
+    }
+}
+
+/// A failed attempt against one lookup provider
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LookupAttempt {
+    pub provider: String,
+    pub error: LookupError,
+}
+
+/// Result of the IPv6-only lookup
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Ipv6Lookup {
+    /// Public IPv6 address, absent when no provider was reachable over IPv6
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Why no address was found, usually because IPv6 is blocked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of the DNS leak probe
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DnsReport {
+    /// Server queried directly, absent when the system resolver was used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// Resolvers that answered, as reported by the whoami name
+    #[serde(default)]
+    pub resolvers: Vec<DnsResolver>,
+    /// Probe names and what they resolved to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<DnsName>,
+    /// Why the resolvers could not be identified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DnsReport {
+    /// Sorted resolver addresses
+    pub fn resolver_ips(&self) -> Vec<&str> {
+        let mut ips: Vec<&str> = self.resolvers.iter().map(|r| r.ip.as_str()).collect();
+        ips.sort();
+        ips
+    }
+
+    /// Sorted, deduplicated resolver ASNs, or the /24 (IPv6: /48) of resolvers without one
+    ///
+    /// Anycast resolvers answer from a different address of the same network on
+    /// almost every query, so only a new network is a change worth notifying.
+    pub fn resolver_networks(&self) -> Vec<String> {
+        let networks: BTreeSet<String> = self
+            .resolvers
+            .iter()
+            .map(|resolver| match resolver.asn {
+                Some(ref asn) => asn.clone(),
+                None => match resolver.ip.parse::<IpAddr>() {
+                    Ok(ip) => {
+                        let prefix = if ip.is_ipv4() { 24 } else { 48 };
+                        IpNet::new(ip, prefix)
+                            .map_or_else(|_| resolver.ip.clone(), |net| net.trunc().to_string())
+                    }
+                    Err(_) => resolver.ip.clone(),
+                },
+            })
+            .collect();
+        networks.into_iter().collect()
+    }
+}
+
+/// A resolver seen by the DNS probe
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DnsResolver {
+    pub ip: String,
+    /// From `ASN_DATABASE`, when loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+}
+
+/// A probe name resolved by the DNS probe
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DnsName {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether the lookup proxy (`IP_LOOKUP_PROXY`) accepts connections
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProxyStatus {
+    /// Proxy URL without credentials
+    pub url: String,
+    pub reachable: bool,
+    /// Time taken to connect, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// VPN tunnel state reported by Gluetun (`/v1/vpn/status` or `/v1/openvpn/status`)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TunnelStatus {
+    /// Absent when the control server could not be asked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<TunnelState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// True while the kill switch probe has the tunnel stopped on purpose
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub probing: bool,
+}
+
+/// State of Gluetun's VPN loop
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelState {
+    Running,
+    Starting,
+    Stopping,
+    Stopped,
+    Crashed,
+    /// A state this version does not know
+    #[serde(other)]
+    Unknown,
+}
+
+impl fmt::Display for TunnelState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TunnelState::Running => "running",
+            TunnelState::Starting => "starting",
+            TunnelState::Stopping => "stopping",
+            TunnelState::Stopped => "stopped",
+            TunnelState::Crashed => "crashed",
+            TunnelState::Unknown => "unknown",
+        })
+    }
+}
+
+/// Gluetun's exit IP compared with the known home (non-VPN) IP
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct KillSwitchStatus {
+    /// Exit IP reported by Gluetun's control server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_ip: Option<String>,
+    /// Number of home IPs and ranges compared, including the one answered by
+    /// `HOME_LOOKUP_URL`; the addresses themselves are never served
+    #[serde(default)]
+    pub home_count: usize,
+    /// True when the exit IP is a home IP, i.e. traffic bypasses the tunnel
+    pub leaking: bool,
+    /// Why the exit or home IP could not be determined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Most recent tunnel-stop probe
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<KillSwitchProbe>,
+}
+
+/// Outcome of stopping the tunnel and trying to reach the internet
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct KillSwitchProbe {
+    pub checked_at: DateTime<Utc>,
+    pub outcome: ProbeOutcome,
+    pub message: String,
+}
+
+/// Whether the firewall blocked traffic while the tunnel was down
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeOutcome {
+    /// The probe request failed, the kill switch blocked it
+    Held,
+    /// The probe request got an answer without the tunnel
+    Leaked,
+    /// The tunnel could not be stopped or restarted
+    Error,
+}
+
+/// Comparison of the answers of every provider, queried concurrently
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Consensus {
+    /// False when any provider saw a different IP or ASN than the one used
+    pub agreed: bool,
+    pub answers: Vec<ProviderAnswer>,
+    /// One entry per difference, e.g. "ifconfig saw IP 5.6.7.8, gluetun saw 1.2.3.4"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disagreements: Vec<String>,
+}
+
+/// What one provider answered in consensus mode
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProviderAnswer {
+    pub provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<LookupError>,
+}
+
+/// Query parameters for /status and /check
+#[derive(Debug, Deserialize, Default)]
+pub struct LookupQuery {
+    /// `?fresh=1` (or `true`) bypasses the lookup cache
+    #[serde(default)]
+    pub fresh: Option<String>,
+}
+
+impl LookupQuery {
+    /// Whether a live lookup was requested
+    pub fn is_fresh(&self) -> bool {
+        matches!(
+            self.fresh.as_deref().map(str::to_lowercase).as_deref(),
+            Some("1" | "true" | "yes")
+        )
+    }
 }
 
 /// Response for /status endpoint
@@ -38,6 +390,10 @@ pub struct StatusResponse {
     #[serde(flatten)]
     pub lookup: LookupResult,
     pub allowed_asns: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub denied_asns: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowed_cidrs: Vec<String>,
     pub configured: bool,
 }
 
@@ -45,18 +401,101 @@ pub struct StatusResponse {
 #[derive(Serialize)]
 pub struct CheckResponse {
     pub ok: bool,
+    /// Worst rule status
+    pub status: RuleStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
+    /// Result of every policy rule
+    pub rules: Vec<RuleResult>,
     #[serde(flatten)]
     pub lookup: LookupResult,
 }
 
+/// Request body for POST and DELETE /admin/asns
+#[derive(Debug, Deserialize)]
+pub struct AsnRequest {
+    pub asn: String,
+}
+
+/// Response for /admin/asns
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminAsnsResponse {
+    /// Effective allowlist used by /check
+    pub allowed_asns: Vec<String>,
+    /// ASNs added at runtime on top of the configuration
+    pub added: Vec<String>,
+    /// Configured ASNs removed at runtime
+    pub removed: Vec<String>,
+    /// Whether the overrides were written to the state file
+    pub persisted: bool,
+}
+
+/// Request body for POST /admin/asns/promote
+#[derive(Debug, Default, Deserialize)]
+pub struct PromoteRequest {
+    /// Observed ASNs to promote, all observed ASNs if empty
+    #[serde(default)]
+    pub asns: Vec<String>,
+}
+
+/// Response for /asns/observed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ObservedAsnsResponse {
+    pub window_hours: u64,
+    pub observed: Vec<ObservedAsnEntry>,
+}
+
+/// An observed ASN and whether it is already allowed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ObservedAsnEntry {
+    #[serde(flatten)]
+    pub observed: ObservedAsn,
+    pub allowed: bool,
+}
+
+/// Error body returned by the admin API
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
-    pub allowed_asns: Arc<HashSet<String>>,
+    pub allowed_asns: Arc<AllowList>,
+    /// Rules evaluated by /check, with the deny-list and geofence they use
+    pub policy: Arc<Policy>,
     pub client: reqwest::Client,
-    pub ntfy_url: Option<String>,
+    pub ntfy_urls: Arc<Vec<String>>,
     pub gluetun_url: Option<String>,
     pub gluetun_api_key: Option<String>,
+    pub providers: Arc<Vec<Box<dyn IpLookupProvider>>>,
+    pub lookup_cache: Arc<LookupCache>,
+    /// Merged configuration with secrets redacted, served on /config
+    pub effective_config: Arc<RwLock<serde_json::Value>>,
+    /// Bearer token for /admin endpoints, the admin API is disabled without it
+    pub admin_token: Option<String>,
+    /// Where admin API allowlist changes are persisted
+    pub admin_state_file: Option<PathBuf>,
+    /// Records observed ASNs when learn mode is enabled
+    pub asn_observer: Option<Arc<AsnObserver>>,
+}
+
+impl AppState {
+    /// Look up the current public IP through the shared cache
+    ///
+    /// Live results are recorded by the ASN observer in learn mode.
+    pub async fn lookup(&self, fresh: bool) -> LookupResult {
+        let info = self
+            .lookup_cache
+            .get(&self.client, &self.providers, fresh)
+            .await;
+
+        if let Some(ref observer) = self.asn_observer {
+            if info.cache_age_secs.is_none() {
+                observer.record(&info);
+            }
+        }
+        info
+    }
 }
@@ -1,18 +1,27 @@
 //! VPN Change Detector
 //!
-//! Monitors VPN connection for changes (IP, ASN, location).
+//! Monitors VPN connection for changes (IP, ASN, location, DNS resolver ASNs or networks).
 //! Sends notifications when changes are detected.
+//! Raises an immediate leak alert when the ASN is a home ISP ASN (`VPN_DENIED_ASNS`),
+//! including on the first check.
+//! With kill switch verification, alerts when Gluetun's exit IP equals the home IP.
 //! Runs continuously at configured check interval.
 
-use crate::{ip_lookup, models::AppState, notification};
+use crate::{
+    allowlist::AsnVerdict,
+    metrics,
+    models::{AppState, LookupResult},
+    notification,
+};
 use tokio::time::Duration;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
 /// VPN state tracker for change detection
 struct VpnState {
     ip: Option<String>,
     country: Option<String>,
     asn: Option<String>,
+    dns: Option<String>,
 }
 
 impl VpnState {
@@ -21,6 +30,7 @@ impl VpnState {
             ip: None,
             country: None,
             asn: None,
+            dns: None,
         }
     }
 
@@ -30,6 +40,7 @@ impl VpnState {
         current_ip: &Option<String>,
         current_country: &Option<String>,
         current_asn: &Option<String>,
+        current_dns: &Option<String>,
     ) -> Option<String> {
         let mut changes = Vec::new();
 
@@ -37,6 +48,7 @@ impl VpnState {
         if let Some(ip) = current_ip {
             if let Some(ref prev_ip) = self.ip {
                 if ip != prev_ip {
+                    metrics::change_detected("ip");
                     changes.push(format!("IP: {} → {}", prev_ip, ip));
                     self.ip = Some(ip.clone());
                 }
@@ -49,6 +61,7 @@ impl VpnState {
         if let Some(country) = current_country {
             if let Some(ref prev_country) = self.country {
                 if country != prev_country {
+                    metrics::change_detected("country");
                     changes.push(format!("Country: {} → {}", prev_country, country));
                     self.country = Some(country.clone());
                 }
@@ -61,6 +74,7 @@ impl VpnState {
         if let Some(asn) = current_asn {
             if let Some(ref prev_asn) = self.asn {
                 if asn != prev_asn {
+                    metrics::change_detected("asn");
                     changes.push(format!("ASN: {} → {}", prev_asn, asn));
                     self.asn = Some(asn.clone());
                 }
@@ -69,6 +83,19 @@ impl VpnState {
             }
         }
 
+        // Check DNS resolver change - only clone if changed
+        if let Some(dns) = current_dns {
+            if let Some(ref prev_dns) = self.dns {
+                if dns != prev_dns {
+                    metrics::change_detected("dns");
+                    changes.push(format!("DNS resolvers: {} → {}", prev_dns, dns));
+                    self.dns = Some(dns.clone());
+                }
+            } else {
+                self.dns = Some(dns.clone());
+            }
+        }
+
         if changes.is_empty() {
             None
         } else {
@@ -79,13 +106,10 @@ impl VpnState {
 
 /// Start VPN change detection and notifications
 pub async fn start_change_detector(state: AppState, interval_minutes: u64) {
-    let ntfy_url = match &state.ntfy_url {
-        Some(url) => url.clone(),
-        None => {
-            warn!("NTFY_URL not configured, change detection disabled");
-            return;
-        }
-    };
+    if state.ntfy_urls.is_empty() {
+        warn!("NTFY_URL not configured, change detection disabled");
+        return;
+    }
 
     info!(
         "Starting change detector (checking every {} minutes)",
@@ -98,56 +122,125 @@ pub async fn start_change_detector(state: AppState, interval_minutes: u64) {
     let mut vpn_state = VpnState::new();
     let mut interval = tokio::time::interval(Duration::from_secs(interval_minutes * 60)); // 5 minutes
 
-    // First check: establish baseline (don't send notification)
-    let info = ip_lookup::lookup(
-        &state.client,
-        state.gluetun_url.as_deref(),
-        state.gluetun_api_key.as_deref(),
-    )
-    .await;
+    // First check: establish baseline (only a leak is notified)
+    let info = state.lookup(false).await;
+    let mut leaking = false;
+    let mut bypassing = check_kill_switch(&state, &info, false).await;
 
     if info.error.is_none() {
         // Initialize baseline using detect_changes
-        vpn_state.detect_changes(&info.ip, &info.country, &info.asn);
+        vpn_state.detect_changes(&info.ip, &info.country, &info.asn, &dns_resolvers(&info));
         info!(
-            "Baseline established: IP={:?}, Country={:?}, ASN={:?}",
-            vpn_state.ip, vpn_state.country, vpn_state.asn
+            "Baseline established: IP={:?}, Country={:?}, ASN={:?}, DNS={:?}",
+            vpn_state.ip, vpn_state.country, vpn_state.asn, vpn_state.dns
         );
+        leaking = check_leak(&state, &info, leaking, None).await;
     }
 
     loop {
         interval.tick().await;
 
         debug!("Change detector: performing check");
-        let info = ip_lookup::lookup(
-            &state.client,
-            state.gluetun_url.as_deref(),
-            state.gluetun_api_key.as_deref(),
-        )
-        .await;
+        let info = state.lookup(false).await;
+        bypassing = check_kill_switch(&state, &info, bypassing).await;
 
         if info.error.is_none() {
-            if let Some(change_msg) = vpn_state.detect_changes(&info.ip, &info.country, &info.asn) {
+            let change_msg =
+                vpn_state.detect_changes(&info.ip, &info.country, &info.asn, &dns_resolvers(&info));
+            if let Some(ref change_msg) = change_msg {
                 info!(
                     "VPN server change detected: {}",
                     change_msg.replace('\n', ", ")
                 );
+            }
+
+            let was_leaking = leaking;
+            leaking = check_leak(&state, &info, leaking, change_msg.as_deref()).await;
 
+            // A new leak was already notified together with the change
+            if leaking && !was_leaking {
+                continue;
+            }
+
+            if let Some(change_msg) = change_msg {
                 // Send immediate notification about the change
-                if let Err(e) = notification::send_notification(
-                    &state.client,
-                    &ntfy_url,
-                    &info,
-                    &state.allowed_asns,
-                    Some(&change_msg),
-                )
-                .await
-                {
-                    warn!("Failed to send change notification: {}", e);
-                }
+                notify(&state, &info, Some(&change_msg)).await;
             }
         } else if let Some(err) = info.error {
             warn!("Change detector lookup failed: {}", err);
         }
     }
 }
+
+/// Resolver ASNs or networks seen by the DNS probe, when it ran and answered
+fn dns_resolvers(info: &LookupResult) -> Option<String> {
+    info.dns
+        .as_ref()
+        .filter(|dns| dns.error.is_none() && !dns.resolvers.is_empty())
+        .map(|dns| dns.resolver_networks().join(", "))
+}
+
+/// Alert when traffic starts leaking via a home ISP ASN, returning whether it is leaking now
+async fn check_leak(
+    state: &AppState,
+    info: &LookupResult,
+    was_leaking: bool,
+    change_details: Option<&str>,
+) -> bool {
+    let verdict = AsnVerdict::of(
+        info.asn.as_deref(),
+        &state.allowed_asns.snapshot(),
+        &state.policy.denied_asns,
+    );
+    let leaking = verdict == AsnVerdict::Denied;
+
+    if leaking && !was_leaking {
+        error!(
+            "Traffic leaking via home ISP: IP={:?}, ASN={:?}",
+            info.ip, info.asn
+        );
+        notify(state, info, change_details).await;
+    } else if was_leaking && !leaking {
+        info!("Home ISP leak resolved, ASN={:?}", info.asn);
+    }
+
+    leaking
+}
+
+/// Alert when Gluetun's exit IP becomes a home IP, returning whether it is one now
+async fn check_kill_switch(state: &AppState, info: &LookupResult, was_bypassing: bool) -> bool {
+    let Some(ref kill_switch) = info.kill_switch else {
+        return false;
+    };
+
+    if kill_switch.leaking && !was_bypassing {
+        let details = format!(
+            "VPN exit IP {} is the home IP, traffic bypasses the tunnel",
+            kill_switch.exit_ip.as_deref().unwrap_or("unknown")
+        );
+        if let Err(e) =
+            notification::send_kill_switch_alert(&state.client, &state.ntfy_urls, &details).await
+        {
+            warn!("Failed to send kill switch alert: {}", e);
+        }
+    } else if was_bypassing && !kill_switch.leaking {
+        info!("Gluetun exit IP is no longer a home IP");
+    }
+
+    kill_switch.leaking
+}
+
+async fn notify(state: &AppState, info: &LookupResult, change_details: Option<&str>) {
+    if let Err(e) = notification::send_to_all(
+        &state.client,
+        &state.ntfy_urls,
+        info,
+        &state.allowed_asns.snapshot(),
+        &state.policy,
+        change_details,
+    )
+    .await
+    {
+        warn!("Failed to send change notification: {}", e);
+    }
+}
@@ -0,0 +1,44 @@
+//! Kill Switch Probe
+//!
+//! Periodically stops the tunnel through Gluetun's control server, checks that
+//! traffic is blocked while it is down and starts it again.
+//! Sends an urgent notification when traffic got out or the tunnel could not be
+//! stopped or restarted. The first probe runs one interval after startup.
+
+use crate::{
+    models::{AppState, ProbeOutcome},
+    notification,
+};
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+/// Start the periodic kill switch probe
+pub async fn start_kill_switch_probe(state: AppState, interval_hours: u64) {
+    let Some(kill_switch) = state.lookup_cache.kill_switch() else {
+        return;
+    };
+
+    info!(
+        "Starting kill switch probe (every {} hours, the VPN is briefly stopped)",
+        interval_hours
+    );
+
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_hours * 60 * 60));
+    // The first tick completes immediately, do not stop the tunnel right at startup
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let probe = kill_switch.probe().await;
+        if probe.outcome == ProbeOutcome::Held || state.ntfy_urls.is_empty() {
+            continue;
+        }
+        if let Err(e) =
+            notification::send_kill_switch_alert(&state.client, &state.ntfy_urls, &probe.message)
+                .await
+        {
+            warn!("Failed to send kill switch alert: {}", e);
+        }
+    }
+}
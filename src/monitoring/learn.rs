@@ -0,0 +1,91 @@
+//! ASN Learn Mode
+//!
+//! Records every ASN seen by live lookups over a rolling window, so the
+//! allowlist can be built from what the VPN actually uses instead of
+//! restarting the stack to sample exit servers.
+//! Observations are kept in memory and served on `/asns/observed`; the admin
+//! API can promote them into the allowlist.
+
+use crate::models::LookupResult;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Mutex};
+
+/// An ASN seen by at least one lookup within the window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObservedAsn {
+    pub asn: String,
+    /// Most recently reported organization
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Number of live lookups that returned this ASN
+    pub count: u64,
+}
+
+/// In-memory record of observed ASNs
+pub struct AsnObserver {
+    window: TimeDelta,
+    seen: Mutex<HashMap<String, ObservedAsn>>,
+}
+
+impl AsnObserver {
+    /// Observer forgetting ASNs not seen for `window_hours`
+    ///
+    /// Windows beyond what `TimeDelta` holds are clamped rather than panicking.
+    pub fn new(window_hours: u64) -> Self {
+        Self {
+            window: i64::try_from(window_hours)
+                .ok()
+                .and_then(TimeDelta::try_hours)
+                .unwrap_or(TimeDelta::MAX),
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Length of the rolling window in hours
+    pub fn window_hours(&self) -> u64 {
+        self.window.num_hours() as u64
+    }
+
+    /// Record the ASN of a successful lookup
+    pub fn record(&self, info: &LookupResult) {
+        let Some(ref asn) = info.asn else {
+            return;
+        };
+        let at = info.checked_at.unwrap_or_else(Utc::now);
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.entry(asn.clone())
+            .and_modify(|entry| {
+                entry.count += 1;
+                entry.first_seen = entry.first_seen.min(at);
+                entry.last_seen = entry.last_seen.max(at);
+                if info.org.is_some() {
+                    entry.org = info.org.clone();
+                }
+            })
+            .or_insert_with(|| ObservedAsn {
+                asn: asn.clone(),
+                org: info.org.clone(),
+                first_seen: at,
+                last_seen: at,
+                count: 1,
+            });
+    }
+
+    /// ASNs seen within the window, most frequent first
+    pub fn observed(&self) -> Vec<ObservedAsn> {
+        let cutoff = Utc::now()
+            .checked_sub_signed(self.window)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, entry| entry.last_seen >= cutoff);
+
+        let mut observed: Vec<ObservedAsn> = seen.values().cloned().collect();
+        observed.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.asn.cmp(&b.asn)));
+        observed
+    }
+}
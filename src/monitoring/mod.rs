@@ -1,10 +1,17 @@
 //! Monitoring Module
 //!
 //! Background tasks for VPN monitoring and notifications.
-//! Includes periodic notifier and change detector.
+//! Includes periodic notifier, change detector, config reloader, ASN learn mode and
+//! the kill switch probe.
 
 mod change_detector;
+mod kill_switch;
+mod learn;
 mod periodic;
+mod reload;
 
 pub use change_detector::start_change_detector;
+pub use kill_switch::start_kill_switch_probe;
+pub use learn::{AsnObserver, ObservedAsn};
 pub use periodic::start_periodic_notifier;
+pub use reload::{reload_allowlist, start_config_reloader};
@@ -4,25 +4,24 @@
 //! Waits for VPN connection to establish before first notification.
 //! Runs continuously in background.
 
-use crate::{ip_lookup, models::AppState, notification};
+use crate::{config::redact_url, models::AppState, notification};
 use tokio::time::Duration;
 use tracing::{error, info, warn};
 
 /// Start periodic health check notifications
 pub async fn start_periodic_notifier(state: AppState, interval_hours: u64) {
-    let ntfy_url = match &state.ntfy_url {
-        Some(url) => url.clone(),
-        None => {
-            warn!("NTFY_URL not configured, notifications disabled");
-            return;
-        }
-    };
+    if state.ntfy_urls.is_empty() {
+        warn!("NTFY_URL not configured, notifications disabled");
+        return;
+    }
 
     info!(
         "Starting periodic notifier (every {} hours)",
         interval_hours
     );
-    info!("Sending notifications to: {}", ntfy_url);
+    for ntfy_url in state.ntfy_urls.iter() {
+        info!("Sending notifications to: {}", redact_url(ntfy_url));
+    }
 
     // Wait for Gluetun to be ready before first notification
     if state.gluetun_url.is_some() {
@@ -35,18 +34,14 @@ pub async fn start_periodic_notifier(state: AppState, interval_hours: u64) {
     loop {
         interval.tick().await;
 
-        let info = ip_lookup::lookup(
-            &state.client,
-            state.gluetun_url.as_deref(),
-            state.gluetun_api_key.as_deref(),
-        )
-        .await;
+        let info = state.lookup(false).await;
 
-        if let Err(e) = notification::send_notification(
+        if let Err(e) = notification::send_to_all(
             &state.client,
-            &ntfy_url,
+            &state.ntfy_urls,
             &info,
-            &state.allowed_asns,
+            &state.allowed_asns.snapshot(),
+            &state.policy,
             None,
         )
         .await
@@ -0,0 +1,110 @@
+//! Configuration Reloader
+//!
+//! Reloads the ASN allowlist without restarting on SIGHUP or when the config file changes.
+//! The allowlist is swapped atomically for handlers and background tasks, so the
+//! change detector keeps its baseline.
+//! Invalid configurations are rejected and the current allowlist is kept.
+
+use crate::{config::Config, models::AppState, notification};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+/// How often the config file is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reload configuration and swap the allowlist
+///
+/// Returns a description of the allowlist change, or the validation errors.
+pub async fn reload_allowlist(
+    state: &AppState,
+    config_path: Option<&Path>,
+) -> Result<String, String> {
+    let config = Config::load(config_path)?;
+
+    // Validate against the database already in use, ASN_DATABASE needs a restart
+    let (report, _) = config.validate_with_database(state.policy.asn_database.clone());
+    for warning in &report.warnings {
+        warn!("Config reload: {}", warning);
+    }
+    if !report.is_ok() {
+        return Err(report.errors.join("; "));
+    }
+
+    // Only the allowlist is reloaded, other settings still need a restart
+    let redacted = config.redacted();
+    if let Some(vpn) = state
+        .effective_config
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_mut("vpn")
+    {
+        vpn["allowed_asns"] = redacted["vpn"]["allowed_asns"].clone();
+    }
+
+    let diff = state.allowed_asns.replace(config.allowed_asns);
+    info!("Allowlist reloaded: {}", diff);
+
+    if !diff.is_empty() && config.notify_on_reload && !state.ntfy_urls.is_empty() {
+        if let Err(e) =
+            notification::send_allowlist_change(&state.client, &state.ntfy_urls, &diff).await
+        {
+            warn!("Failed to send allowlist change notification: {}", e);
+        }
+    }
+
+    Ok(diff.to_string())
+}
+
+/// Watch for SIGHUP and config file changes, reloading the allowlist on either
+pub async fn start_config_reloader(state: AppState, config_path: Option<PathBuf>) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Failed to install SIGHUP handler, reload disabled: {}", e);
+            return;
+        }
+    };
+
+    match config_path {
+        Some(ref path) => info!(
+            "Config reload enabled (SIGHUP or changes to {})",
+            path.display()
+        ),
+        None => info!("Config reload enabled (SIGHUP)"),
+    }
+
+    let mut last_modified = config_path.as_deref().and_then(modified);
+    let mut poll = tokio::time::interval(CONFIG_POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {
+                info!("SIGHUP received, reloading configuration");
+            }
+            _ = poll.tick(), if config_path.is_some() => {
+                let current = config_path.as_deref().and_then(modified);
+                if current == last_modified {
+                    continue;
+                }
+                last_modified = current;
+                info!("Config file changed, reloading configuration");
+            }
+        }
+
+        if let Err(e) = reload_allowlist(&state, config_path.as_deref()).await {
+            error!("Config reload failed, keeping current allowlist: {}", e);
+        }
+    }
+}
+
+/// Last modification time of a file, `None` if it cannot be read
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
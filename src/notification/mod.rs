@@ -1,8 +1,9 @@
 //! Notification Module
 //!
 //! Sends notifications to ntfy.sh or compatible services.
-//! Exports the send_notification function.
+//! Exports the send_notification, send_to_all, send_allowlist_change and
+//! send_kill_switch_alert functions.
 
 mod ntfy;
 
-pub use ntfy::send_notification;
+pub use ntfy::{send_allowlist_change, send_kill_switch_alert, send_notification, send_to_all};
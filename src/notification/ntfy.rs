@@ -2,9 +2,17 @@
 //!
 //! Sends rich notifications to ntfy.sh with VPN status information.
 //! Includes formatted messages with emojis, priority levels, and tags.
-//! Supports both periodic updates and change notifications.
+//! Supports periodic updates, change notifications and allowlist reloads.
+//! A home ISP ASN (deny-list) is sent as an urgent leak alert, as is a failed kill switch.
+//! With `VPN_ALLOWED_CIDRS` set, the message says whether the exit IP is in range.
 
-use crate::models::LookupResult;
+use crate::{
+    allowlist::{AsnDiff, AsnVerdict},
+    config::redact_url,
+    metrics,
+    models::LookupResult,
+    policy::Policy,
+};
 use reqwest::Client;
 use std::collections::HashSet;
 use tokio::time::Duration;
@@ -16,22 +24,120 @@ pub async fn send_notification(
     ntfy_url: &str,
     info: &LookupResult,
     allowed_asns: &HashSet<String>,
+    policy: &Policy,
     change_details: Option<&str>,
 ) -> Result<(), String> {
-    let message = build_message(info, allowed_asns, change_details);
-    let title = determine_title(info, allowed_asns, change_details);
-    let priority = determine_priority(info, allowed_asns, change_details);
+    let verdict = AsnVerdict::of(info.asn.as_deref(), allowed_asns, &policy.denied_asns);
+    let ip_allowed = policy.ip_allowed(info);
+    let allowed = is_allowed(verdict, ip_allowed, allowed_asns.is_empty());
+    let message = build_message(info, verdict, allowed, ip_allowed, change_details);
+    let title = determine_title(info, verdict, allowed, change_details);
+    let priority = determine_priority(info, verdict, allowed, change_details);
+    let tags = if verdict == AsnVerdict::Denied {
+        "rotating_light,vpn,network"
+    } else {
+        "vpn,network"
+    };
+
+    post_with_retry(client, ntfy_url, title, priority, tags, &message).await
+}
+
+/// Notify every configured ntfy URL that the allowlist was reloaded
+pub async fn send_allowlist_change(
+    client: &Client,
+    ntfy_urls: &[String],
+    diff: &AsnDiff,
+) -> Result<(), String> {
+    let mut message = String::from("🔐 VPN ASN allowlist reloaded\n\n");
+    if !diff.added.is_empty() {
+        message.push_str(&format!("➕ Added: {}\n", diff.added.join(", ")));
+    }
+    if !diff.removed.is_empty() {
+        message.push_str(&format!("➖ Removed: {}\n", diff.removed.join(", ")));
+    }
+    message.push_str(&format!(
+        "⏰ Time: {}",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    ));
+
+    post_to_all(
+        client,
+        ntfy_urls,
+        "🔐 VPN Allowlist Changed",
+        "default",
+        "vpn,config",
+        &message,
+    )
+    .await
+}
+
+/// Notify every configured ntfy URL that the kill switch verification failed
+pub async fn send_kill_switch_alert(
+    client: &Client,
+    ntfy_urls: &[String],
+    details: &str,
+) -> Result<(), String> {
+    let message = format!(
+        "🚨 VPN kill switch check failed\n\n{}\n⏰ Time: {}",
+        details,
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    );
+
+    post_to_all(
+        client,
+        ntfy_urls,
+        "🚨 VPN Kill Switch Failed",
+        "urgent",
+        "rotating_light,vpn,network",
+        &message,
+    )
+    .await
+}
 
-    // Retry logic: 3 attempts with exponential backoff
+/// POST a message to every ntfy URL, listing every failed URL (redacted)
+async fn post_to_all(
+    client: &Client,
+    ntfy_urls: &[String],
+    title: &str,
+    priority: &str,
+    tags: &str,
+    message: &str,
+) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for ntfy_url in ntfy_urls {
+        if let Err(e) = post_with_retry(client, ntfy_url, title, priority, tags, message).await {
+            failures.push(format!("{}: {}", redact_url(ntfy_url), e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+/// POST a message to ntfy: 3 attempts with exponential backoff
+async fn post_with_retry(
+    client: &Client,
+    ntfy_url: &str,
+    title: &str,
+    priority: &str,
+    tags: &str,
+    message: &str,
+) -> Result<(), String> {
     for attempt in 1..=3 {
         let request = client
             .post(ntfy_url)
             .header("Title", title)
             .header("Priority", priority)
-            .header("Tags", "vpn,network")
-            .body(message.clone());
+            .header("Tags", tags)
+            .body(message.to_string());
 
-        match request.send().await {
+        let outcome = request.send().await;
+        metrics::notification_attempt(matches!(&outcome, Ok(resp) if resp.status().is_success()));
+
+        match outcome {
             Ok(resp) if resp.status().is_success() => {
                 info!("Notification sent successfully");
                 return Ok(());
@@ -62,25 +168,74 @@ pub async fn send_notification(
     Err("All retry attempts failed".to_string())
 }
 
+/// Send the same notification to every configured ntfy URL
+///
+/// Returns an error listing every failed URL (redacted) if any send failed.
+pub async fn send_to_all(
+    client: &Client,
+    ntfy_urls: &[String],
+    info: &LookupResult,
+    allowed_asns: &HashSet<String>,
+    policy: &Policy,
+    change_details: Option<&str>,
+) -> Result<(), String> {
+    let mut failures = Vec::new();
+
+    for ntfy_url in ntfy_urls {
+        if let Err(e) =
+            send_notification(client, ntfy_url, info, allowed_asns, policy, change_details).await
+        {
+            failures.push(format!("{}: {}", redact_url(ntfy_url), e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+/// Whether the exit is allowed by ASN and, when set, by IP range
+///
+/// Without an ASN allowlist the IP range alone decides.
+fn is_allowed(verdict: AsnVerdict, ip_allowed: Option<bool>, no_allowed_asns: bool) -> bool {
+    match verdict {
+        AsnVerdict::Denied => false,
+        AsnVerdict::Allowed => ip_allowed != Some(false),
+        AsnVerdict::Unknown => no_allowed_asns && ip_allowed == Some(true),
+    }
+}
+
 /// Build notification message with status and details
 fn build_message(
     info: &LookupResult,
-    allowed_asns: &HashSet<String>,
+    verdict: AsnVerdict,
+    allowed: bool,
+    ip_allowed: Option<bool>,
     change_details: Option<&str>,
 ) -> String {
-    let is_allowed = info
-        .asn
-        .as_ref()
-        .is_some_and(|asn| allowed_asns.contains(asn));
-    let status_emoji = if is_allowed { "✅" } else { "⚠️" };
-    let status_text = if is_allowed { "Allowed" } else { "Not Allowed" };
-    let proton_badge = if is_allowed {
-        "🔒 Proton VPN"
-    } else {
-        "⚡ Unknown Provider"
+    let proton_badge = match verdict {
+        AsnVerdict::Allowed => "🔒 Proton VPN",
+        AsnVerdict::Denied => "🏠 Home ISP",
+        AsnVerdict::Unknown => "⚡ Unknown Provider",
+    };
+    let (status_emoji, status_text) = match verdict {
+        AsnVerdict::Denied => ("🚨", "LEAKING VIA HOME ISP"),
+        _ if allowed => ("✅", "Allowed"),
+        _ => ("⚠️", "Not Allowed"),
+    };
+
+    let ip_range = match ip_allowed {
+        Some(true) => " (in allowed range)",
+        Some(false) => " (OUTSIDE allowed ranges)",
+        None => "",
     };
 
-    let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
+    let timestamp = info
+        .checked_at
+        .unwrap_or_else(chrono::Utc::now)
+        .format("%Y-%m-%d %H:%M:%S UTC");
 
     let org_info = info
         .org
@@ -101,20 +256,84 @@ fn build_message(
         .map(|port| format!("🔌 Port: {}\n", port))
         .unwrap_or_default();
 
+    let source_info = info
+        .source
+        .as_ref()
+        .map(|source| match info.latency_ms {
+            Some(ms) => format!("🛰️ Source: {} ({} ms)\n", source, ms),
+            None => format!("🛰️ Source: {}\n", source),
+        })
+        .unwrap_or_default();
+
+    // Falling back to a later provider means an earlier one (usually Gluetun) failed
+    let fallback_info = if info.is_fallback() {
+        let failed = info
+            .attempts
+            .iter()
+            .map(|a| format!("{} ({})", a.provider, a.error))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("⚠️ Fallback: {} failed\n", failed)
+    } else {
+        String::new()
+    };
+
+    let ipv6_info = match info.ipv6 {
+        Some(ref ipv6) => match (&ipv6.ip, &ipv6.asn) {
+            (Some(ip), Some(asn)) => format!("📍 IPv6: {} ({})\n", ip, asn),
+            (Some(ip), None) => format!("📍 IPv6: {}\n", ip),
+            (None, _) => "📍 IPv6: not reachable\n".to_string(),
+        },
+        None => String::new(),
+    };
+
+    let dns_info = match info.dns {
+        Some(ref dns) if dns.error.is_some() => "🧭 DNS: probe failed\n".to_string(),
+        Some(ref dns) => {
+            let resolvers = dns
+                .resolvers
+                .iter()
+                .map(|r| match r.asn {
+                    Some(ref asn) => format!("{} ({})", r.ip, asn),
+                    None => r.ip.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("🧭 DNS: {}\n", resolvers)
+        }
+        None => String::new(),
+    };
+
+    let consensus_info = match info.consensus {
+        Some(ref consensus) if !consensus.agreed => {
+            format!(
+                "⚠️ Providers disagree: {}\n",
+                consensus.disagreements.join("; ")
+            )
+        }
+        _ => String::new(),
+    };
+
     let change_info = change_details
         .map(|changes| format!("🔄 Changes Detected:\n{}\n\n", changes))
         .unwrap_or_default();
 
     format!(
-        "{} VPN Status Report\n\n{}📍 IP: {}\n🌐 Location: {}\n🔢 ASN: {} ({})\n{}{}{} Status: {}\n⏰ Time: {}",
+        "{} VPN Status Report\n\n{}📍 IP: {}{}\n{}🌐 Location: {}\n🔢 ASN: {} ({})\n{}{}{}{}{}{}{} Status: {}\n⏰ Time: {}",
         status_emoji,
         change_info,
         info.ip.as_deref().unwrap_or("Unknown"),
+        ip_range,
+        ipv6_info,
         location,
         info.asn.as_deref().unwrap_or("Unknown"),
         proton_badge,
         org_info,
         port_info,
+        source_info,
+        dns_info,
+        fallback_info,
+        consensus_info,
         status_emoji,
         status_text,
         timestamp
@@ -124,41 +343,35 @@ fn build_message(
 /// Determine notification title based on status
 fn determine_title(
     info: &LookupResult,
-    allowed_asns: &HashSet<String>,
+    verdict: AsnVerdict,
+    allowed: bool,
     change_details: Option<&str>,
 ) -> &'static str {
-    if change_details.is_some() {
+    if verdict == AsnVerdict::Denied {
+        "🚨 VPN LEAK: Traffic via Home ISP"
+    } else if change_details.is_some() {
         "🔄 VPN Server Changed!"
+    } else if allowed && !info.is_fallback() && !info.is_degraded() {
+        "VPN Health: OK"
     } else {
-        let is_allowed = info
-            .asn
-            .as_ref()
-            .is_some_and(|asn| allowed_asns.contains(asn));
-        if is_allowed {
-            "VPN Health: OK"
-        } else {
-            "VPN Health: Warning"
-        }
+        "VPN Health: Warning"
     }
 }
 
 /// Determine notification priority
 fn determine_priority(
     info: &LookupResult,
-    allowed_asns: &HashSet<String>,
+    verdict: AsnVerdict,
+    allowed: bool,
     change_details: Option<&str>,
 ) -> &'static str {
-    if change_details.is_some() {
+    if verdict == AsnVerdict::Denied {
+        "urgent"
+    } else if change_details.is_some() {
         "high"
+    } else if allowed && !info.is_fallback() && !info.is_degraded() {
+        "default"
     } else {
-        let is_allowed = info
-            .asn
-            .as_ref()
-            .is_some_and(|asn| allowed_asns.contains(asn));
-        if is_allowed {
-            "default"
-        } else {
-            "high"
-        }
+        "high"
     }
 }
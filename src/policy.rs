@@ -0,0 +1,723 @@
+// Policy Module
+//!
+//! Composable rules evaluated by `/check`.
+//!
+//! Each rule looks at the lookup result and produces a pass, warn or fail
+//! result with a message. `/check` returns every result and its HTTP status
+//! follows the worst one, so each deployment picks its own combination with
+//! `CHECK_RULES` instead of changing the handler.
+//!
+//! ## Rules
+//! - `lookup`: a provider answered (warns when a fallback provider was used), always evaluated
+//! - `asn_allow`: the ASN is in `VPN_ALLOWED_ASNS`
+//! - `asn_deny`: the ASN is not a home ISP ASN from `VPN_DENIED_ASNS`
+//! - `location`: the exit country, region and city satisfy the geofence
+//! - `port_forward`: Gluetun reports a forwarded port
+//! - `gluetun_source`: the answer came from Gluetun rather than a public fallback
+//! - `ip_cidr`: the exit IP is inside one of `VPN_ALLOWED_CIDRS`
+//! - `asn_crosscheck`: the reported ASN matches the local `ASN_DATABASE`
+//! - `consensus`: every provider queried in consensus mode saw the same IP and ASN
+//! - `ipv6_leak`: the IPv6 exit, if reachable, is in an allowed range or ASN and not a home ISP
+//! - `dns_leak`: the resolvers seen by the DNS probe are allowed and not a home ISP
+//! - `kill_switch`: Gluetun's exit IP is not the home IP and the last tunnel-stop probe
+//!   was blocked
+//! - `tunnel`: Gluetun reports the VPN tunnel as running, evaluated even when the lookup failed
+//!
+//! A rule is written as `name` or `name:warn`, the latter downgrading its
+//! failure to a warning. Without `CHECK_RULES`, the configured checks are used:
+//! `lookup`, `asn_deny` (if set), `asn_allow`, `location` (if set), `ip_cidr` (if set)
+//! `asn_crosscheck:warn` (if a database is set), `consensus:warn` (in consensus mode)
+//! `ipv6_leak` (with dual-stack lookups), `dns_leak:warn` (with the DNS probe) and
+//! `kill_switch` (with kill switch verification) and `tunnel` (with `GLUETUN_API_URL`).
+//! With only `VPN_ALLOWED_CIDRS` set, `ip_cidr` replaces `asn_allow`, which also
+//! validates answers without an ASN.
+
+use crate::{
+    config::Config,
+    dns::DnsAllowList,
+    geofence::Geofence,
+    ip_lookup::{AsnDatabase, LookupMode},
+    models::{DnsResolver, LookupResult, ProbeOutcome, TunnelState},
+};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, net::IpAddr, str::FromStr, sync::Arc};
+
+/// Outcome of a single rule, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleStatus {
+    /// Not evaluated because the lookup failed
+    Skip,
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The checks a rule can perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Lookup,
+    AsnAllow,
+    AsnDeny,
+    Location,
+    PortForward,
+    GluetunSource,
+    IpCidr,
+    AsnCrosscheck,
+    Consensus,
+    Ipv6Leak,
+    DnsLeak,
+    KillSwitch,
+    Tunnel,
+}
+
+/// A rule and how its failure is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub kind: RuleKind,
+    /// `Fail` by default, `Warn` for rules written as `name:warn`
+    pub on_failure: RuleStatus,
+}
+
+/// Result of one rule, returned by `/check`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleResult {
+    pub rule: String,
+    pub status: RuleStatus,
+    pub message: String,
+}
+
+/// Results of every rule and the worst status among them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyReport {
+    pub status: RuleStatus,
+    pub results: Vec<RuleResult>,
+}
+
+/// Rules and the settings they check against
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+    /// Home ISP ASNs that indicate a leak
+    pub denied_asns: HashSet<String>,
+    /// Country, region and city rules for the exit location
+    pub geofence: Geofence,
+    pub allowed_cidrs: Vec<IpNet>,
+    /// Local database for `asn_crosscheck`, loaded at startup
+    pub asn_database: Option<Arc<AsnDatabase>>,
+    /// Resolvers accepted by `dns_leak`
+    pub dns_allowed: DnsAllowList,
+}
+
+impl RuleKind {
+    const ALL: [RuleKind; 13] = [
+        RuleKind::Lookup,
+        RuleKind::AsnAllow,
+        RuleKind::AsnDeny,
+        RuleKind::Location,
+        RuleKind::PortForward,
+        RuleKind::GluetunSource,
+        RuleKind::IpCidr,
+        RuleKind::AsnCrosscheck,
+        RuleKind::Consensus,
+        RuleKind::Ipv6Leak,
+        RuleKind::DnsLeak,
+        RuleKind::KillSwitch,
+        RuleKind::Tunnel,
+    ];
+
+    /// Name used in `CHECK_RULES` and in results
+    pub fn name(self) -> &'static str {
+        match self {
+            RuleKind::Lookup => "lookup",
+            RuleKind::AsnAllow => "asn_allow",
+            RuleKind::AsnDeny => "asn_deny",
+            RuleKind::Location => "location",
+            RuleKind::PortForward => "port_forward",
+            RuleKind::GluetunSource => "gluetun_source",
+            RuleKind::IpCidr => "ip_cidr",
+            RuleKind::AsnCrosscheck => "asn_crosscheck",
+            RuleKind::Consensus => "consensus",
+            RuleKind::Ipv6Leak => "ipv6_leak",
+            RuleKind::DnsLeak => "dns_leak",
+            RuleKind::KillSwitch => "kill_switch",
+            RuleKind::Tunnel => "tunnel",
+        }
+    }
+}
+
+impl RuleKind {
+    /// Whether the rule reads the provider lookup, and is skipped when it failed
+    ///
+    /// The tunnel state, kill switch, DNS probe and IPv6 exit are gathered on their
+    /// own, so a leak they find still shows when the providers fail.
+    fn reads_lookup(self) -> bool {
+        !matches!(
+            self,
+            RuleKind::Tunnel | RuleKind::KillSwitch | RuleKind::DnsLeak | RuleKind::Ipv6Leak
+        )
+    }
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Rule {
+    /// Rule whose failure fails `/check`
+    pub fn new(kind: RuleKind) -> Self {
+        Self {
+            kind,
+            on_failure: RuleStatus::Fail,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// Parse `name` or `name:warn` / `name:fail`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, severity) = match spec.split_once(':') {
+            Some((name, severity)) => (name.trim(), Some(severity.trim())),
+            None => (spec.trim(), None),
+        };
+
+        let kind = RuleKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = RuleKind::ALL.iter().map(|k| k.name()).collect();
+                format!(
+                    "unknown rule '{}' (expected one of: {})",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+
+        let on_failure = match severity.map(str::to_lowercase).as_deref() {
+            None | Some("fail") => RuleStatus::Fail,
+            Some("warn") => RuleStatus::Warn,
+            Some(other) => {
+                return Err(format!(
+                    "'{}': unknown severity '{}' (expected warn or fail)",
+                    spec, other
+                ))
+            }
+        };
+
+        Ok(Self { kind, on_failure })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.on_failure {
+            RuleStatus::Warn => write!(f, "{}:warn", self.kind),
+            _ => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Parse a rule list, rejecting unknown names and duplicates
+pub fn parse_rules(specs: &[String]) -> Result<Vec<Rule>, String> {
+    let mut rules: Vec<Rule> = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let rule: Rule = spec.parse()?;
+        if rules.iter().any(|r| r.kind == rule.kind) {
+            return Err(format!("rule '{}' is listed more than once", rule.kind));
+        }
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+/// Parse a CIDR, a bare IP address is taken as a single host
+pub fn parse_cidr(value: &str) -> Result<IpNet, String> {
+    let value = value.trim();
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("'{}' is not a valid CIDR (e.g. 10.0.0.0/8)", value))
+}
+
+impl Default for Policy {
+    /// Lookup and allowlist rules only
+    fn default() -> Self {
+        Self::new(HashSet::new(), Geofence::default(), Vec::new())
+    }
+}
+
+impl Policy {
+    /// Policy with the default rules for the given settings
+    pub fn new(
+        denied_asns: HashSet<String>,
+        geofence: Geofence,
+        allowed_cidrs: Vec<IpNet>,
+    ) -> Self {
+        let mut rules = vec![Rule::new(RuleKind::Lookup)];
+        if !denied_asns.is_empty() {
+            rules.push(Rule::new(RuleKind::AsnDeny));
+        }
+        rules.push(Rule::new(RuleKind::AsnAllow));
+        if !geofence.is_empty() {
+            rules.push(Rule::new(RuleKind::Location));
+        }
+        if !allowed_cidrs.is_empty() {
+            rules.push(Rule::new(RuleKind::IpCidr));
+        }
+
+        Self {
+            rules,
+            denied_asns,
+            geofence,
+            allowed_cidrs,
+            asn_database: None,
+            dns_allowed: DnsAllowList::default(),
+        }
+    }
+
+    /// Replace the rules, `lookup` is added first when not listed
+    pub fn with_rules(mut self, mut rules: Vec<Rule>) -> Self {
+        if !rules.iter().any(|r| r.kind == RuleKind::Lookup) {
+            rules.insert(0, Rule::new(RuleKind::Lookup));
+        }
+        self.rules = rules;
+        self
+    }
+
+    /// Build the policy from `CHECK_RULES` and the settings it refers to
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut policy = Self::new(
+            config.denied_asns.clone(),
+            config.geofence.clone(),
+            config.allowed_cidrs.clone(),
+        );
+        policy.dns_allowed = config.dns_allowed.clone();
+        if config.check_rules.is_empty() {
+            // IP ranges alone replace the ASN allowlist
+            if config.allowed_asns.is_empty() && !config.allowed_cidrs.is_empty() {
+                policy.rules.retain(|r| r.kind != RuleKind::AsnAllow);
+            }
+            // A stale database should not take the VPN down on its own
+            if config.asn_database.is_some() {
+                policy.rules.push(Rule {
+                    kind: RuleKind::AsnCrosscheck,
+                    on_failure: RuleStatus::Warn,
+                });
+            }
+            if config.dual_stack {
+                policy.rules.push(Rule::new(RuleKind::Ipv6Leak));
+            }
+            // Gating /check on DNS is opt-in through CHECK_RULES
+            if config.dns_leak_check {
+                policy.rules.push(Rule {
+                    kind: RuleKind::DnsLeak,
+                    on_failure: RuleStatus::Warn,
+                });
+            }
+            if config.kill_switch_enabled() {
+                policy.rules.push(Rule::new(RuleKind::KillSwitch));
+            }
+            if config.gluetun_url.is_some() {
+                policy.rules.push(Rule::new(RuleKind::Tunnel));
+            }
+            // Disagreeing providers degrade the result rather than fail it
+            if config.lookup_mode == LookupMode::Consensus {
+                policy.rules.push(Rule {
+                    kind: RuleKind::Consensus,
+                    on_failure: RuleStatus::Warn,
+                });
+            }
+            return Ok(policy);
+        }
+        Ok(policy.with_rules(parse_rules(&config.check_rules)?))
+    }
+
+    /// Whether the policy includes a rule
+    pub fn has_rule(&self, kind: RuleKind) -> bool {
+        self.rules.iter().any(|r| r.kind == kind)
+    }
+
+    /// Whether the exit IP is inside an allowed CIDR, `None` when no ranges are set
+    ///
+    /// A missing or unparsable IP is not allowed.
+    pub fn ip_allowed(&self, info: &LookupResult) -> Option<bool> {
+        if self.allowed_cidrs.is_empty() {
+            return None;
+        }
+        Some(info.ip.as_deref().is_some_and(|ip| self.in_cidrs(ip)))
+    }
+
+    /// Why a DNS resolver is not acceptable, `None` when it is
+    ///
+    /// Without `DNS_ALLOWED_RESOLVERS` or `DNS_ALLOWED_RESOLVER_ASNS`, resolvers in
+    /// the VPN's own ASNs are accepted.
+    fn resolver_problem(
+        &self,
+        resolver: &DnsResolver,
+        allowed_asns: &HashSet<String>,
+    ) -> Option<String> {
+        let asn = resolver.asn.as_deref();
+        if let Some(asn) = asn.filter(|asn| self.denied_asns.contains(*asn)) {
+            return Some(format!(
+                "DNS leaking via home ISP resolver {} ({})",
+                resolver.ip, asn
+            ));
+        }
+        let in_range = resolver.ip.parse::<IpAddr>().is_ok_and(|addr| {
+            let addr = addr.to_canonical();
+            self.dns_allowed
+                .resolvers
+                .iter()
+                .any(|net| net.contains(&addr))
+        });
+        if in_range {
+            return None;
+        }
+        let asns = if self.dns_allowed.is_empty() {
+            allowed_asns
+        } else {
+            &self.dns_allowed.asns
+        };
+        match asn {
+            Some(asn) if asns.contains(asn) => None,
+            Some(asn) => Some(format!(
+                "DNS resolver {} ({}) is not allowed",
+                resolver.ip, asn
+            )),
+            None => Some(format!(
+                "DNS resolver {} is not allowed (ASN unknown)",
+                resolver.ip
+            )),
+        }
+    }
+
+    /// Whether an address is inside an allowed CIDR
+    fn in_cidrs(&self, ip: &str) -> bool {
+        // IPv4-mapped IPv6 addresses (::ffff:1.2.3.4) match IPv4 ranges
+        ip.parse::<IpAddr>().is_ok_and(|addr| {
+            let addr = addr.to_canonical();
+            self.allowed_cidrs.iter().any(|net| net.contains(&addr))
+        })
+    }
+
+    /// Evaluate every rule against a lookup result
+    pub fn evaluate(&self, info: &LookupResult, allowed_asns: &HashSet<String>) -> PolicyReport {
+        let results: Vec<RuleResult> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let (status, message) = match self.check(rule.kind, info, allowed_asns) {
+                    Check::Pass(message) => (RuleStatus::Pass, message),
+                    Check::Warn(message) => (RuleStatus::Warn, message),
+                    Check::Fail(message) => (rule.on_failure, message),
+                    Check::Skip => (RuleStatus::Skip, "lookup failed".to_string()),
+                };
+                RuleResult {
+                    rule: rule.kind.name().to_string(),
+                    status,
+                    message,
+                }
+            })
+            .collect();
+
+        let status = results
+            .iter()
+            .map(|r| r.status)
+            .max()
+            .unwrap_or(RuleStatus::Pass)
+            .max(RuleStatus::Pass);
+
+        PolicyReport { status, results }
+    }
+
+    fn check(&self, kind: RuleKind, info: &LookupResult, allowed_asns: &HashSet<String>) -> Check {
+        if let Some(error) = info.error.as_ref().filter(|_| kind.reads_lookup()) {
+            return match kind {
+                // Tell a broken proxy apart from a broken tunnel
+                RuleKind::Lookup => match info.proxy {
+                    Some(ref proxy) if !proxy.reachable => Check::Fail(format!(
+                        "lookup proxy {} unreachable: {}",
+                        proxy.url,
+                        proxy.error.as_deref().unwrap_or("unknown error")
+                    )),
+                    _ => Check::Fail(error.clone()),
+                },
+                _ => Check::Skip,
+            };
+        }
+
+        match kind {
+            RuleKind::Lookup => {
+                let source = info.source.as_deref().unwrap_or("unknown");
+                if info.is_fallback() {
+                    let failed: Vec<&str> =
+                        info.attempts.iter().map(|a| a.provider.as_str()).collect();
+                    Check::Warn(format!(
+                        "answered by fallback provider {} after {} failed",
+                        source,
+                        failed.join(", ")
+                    ))
+                } else {
+                    Check::Pass(format!("answered by {}", source))
+                }
+            }
+            RuleKind::AsnAllow => match info.asn.as_deref() {
+                _ if allowed_asns.is_empty() => Check::Fail("VPN_ALLOWED_ASNS not set".to_string()),
+                Some(asn) if allowed_asns.contains(asn) => {
+                    Check::Pass(format!("ASN {} is allowed", asn))
+                }
+                _ => Check::Fail("ASN not allowed".to_string()),
+            },
+            RuleKind::AsnDeny => match info.asn.as_deref() {
+                Some(asn) if self.denied_asns.contains(asn) => {
+                    Check::Fail("traffic leaking via home ISP".to_string())
+                }
+                _ => Check::Pass("ASN is not a home ISP ASN".to_string()),
+            },
+            RuleKind::Location => {
+                let violations = self.geofence.violations(info);
+                if violations.is_empty() {
+                    Check::Pass("exit location is within the geofence".to_string())
+                } else {
+                    Check::Fail(violations.join("; "))
+                }
+            }
+            RuleKind::PortForward => match info.port_forwarded {
+                Some(port) => Check::Pass(format!("port {} is forwarded", port)),
+                None => Check::Fail("no forwarded port".to_string()),
+            },
+            RuleKind::GluetunSource => match info.source.as_deref() {
+                Some("gluetun") => Check::Pass("answered by Gluetun".to_string()),
+                Some(source) => Check::Fail(format!("answered by {} instead of Gluetun", source)),
+                None => Check::Fail("lookup source unknown".to_string()),
+            },
+            RuleKind::IpCidr => {
+                let Some(ip) = info.ip.as_deref() else {
+                    return Check::Fail("exit IP unknown".to_string());
+                };
+                if ip.parse::<IpAddr>().is_err() {
+                    return Check::Fail(format!("IP '{}' is not a valid address", ip));
+                }
+                if self.ip_allowed(info) == Some(true) {
+                    Check::Pass(format!("IP {} is in VPN_ALLOWED_CIDRS", ip))
+                } else {
+                    Check::Fail(format!("IP {} is not in VPN_ALLOWED_CIDRS", ip))
+                }
+            }
+            RuleKind::AsnCrosscheck => {
+                let Some(ref database) = self.asn_database else {
+                    return Check::Fail("ASN_DATABASE not loaded".to_string());
+                };
+                let Some(addr) = info.ip.as_deref().and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+                    return Check::Fail("exit IP unknown".to_string());
+                };
+                let Some(record) = database.resolve(addr) else {
+                    return Check::Warn(format!("IP {} is not in the local ASN database", addr));
+                };
+                match info.asn.as_deref() {
+                    Some(asn) if asn == record.asn => {
+                        Check::Pass(format!("ASN {} matches the local ASN database", asn))
+                    }
+                    Some(asn) => Check::Fail(format!(
+                        "{} reported {}, local ASN database says {}",
+                        info.source.as_deref().unwrap_or("lookup"),
+                        asn,
+                        record.asn
+                    )),
+                    None => Check::Fail(format!(
+                        "no ASN reported, local ASN database says {}",
+                        record.asn
+                    )),
+                }
+            }
+            RuleKind::Ipv6Leak => {
+                let Some(ref ipv6) = info.ipv6 else {
+                    return Check::Fail("dual-stack lookups not enabled".to_string());
+                };
+                let Some(ref ip) = ipv6.ip else {
+                    return Check::Pass("IPv6 is not reachable".to_string());
+                };
+                match ipv6.asn.as_deref() {
+                    Some(asn) if self.denied_asns.contains(asn) => Check::Fail(format!(
+                        "IPv6 traffic leaking via home ISP ({} on {})",
+                        ip, asn
+                    )),
+                    _ if self.in_cidrs(ip) => {
+                        Check::Pass(format!("IPv6 {} is in VPN_ALLOWED_CIDRS", ip))
+                    }
+                    Some(asn) if allowed_asns.contains(asn) => {
+                        Check::Pass(format!("IPv6 {} exits via allowed ASN {}", ip, asn))
+                    }
+                    Some(asn) => Check::Fail(format!(
+                        "IPv6 {} is reachable via ASN {}, which is not allowed",
+                        ip, asn
+                    )),
+                    None => Check::Fail(format!("IPv6 {} is reachable via an unknown ASN", ip)),
+                }
+            }
+            RuleKind::DnsLeak => {
+                let Some(ref dns) = info.dns else {
+                    return Check::Fail("DNS probe not enabled".to_string());
+                };
+                if let Some(ref error) = dns.error {
+                    return Check::Fail(format!("DNS probe failed: {}", error));
+                }
+                let problems: Vec<String> = dns
+                    .resolvers
+                    .iter()
+                    .filter_map(|resolver| self.resolver_problem(resolver, allowed_asns))
+                    .collect();
+                if !problems.is_empty() {
+                    return Check::Fail(problems.join("; "));
+                }
+                let failed: Vec<&str> = dns
+                    .names
+                    .iter()
+                    .filter(|name| name.error.is_some())
+                    .map(|name| name.name.as_str())
+                    .collect();
+                let resolvers = dns.resolver_ips().join(", ");
+                if failed.is_empty() {
+                    Check::Pass(format!("DNS resolvers {} are allowed", resolvers))
+                } else {
+                    Check::Warn(format!(
+                        "DNS resolvers {} are allowed, but {} failed to resolve",
+                        resolvers,
+                        failed.join(", ")
+                    ))
+                }
+            }
+            RuleKind::KillSwitch => {
+                let Some(ref kill_switch) = info.kill_switch else {
+                    return Check::Fail("kill switch verification not enabled".to_string());
+                };
+                let exit_ip = kill_switch.exit_ip.as_deref().unwrap_or("unknown");
+                if kill_switch.leaking {
+                    return Check::Fail(format!(
+                        "VPN exit IP {} is the home IP, traffic bypasses the tunnel",
+                        exit_ip
+                    ));
+                }
+                match kill_switch.probe {
+                    Some(ref probe) if probe.outcome == ProbeOutcome::Leaked => {
+                        return Check::Fail(format!("kill switch did not hold: {}", probe.message));
+                    }
+                    Some(ref probe) if probe.outcome == ProbeOutcome::Error => {
+                        return Check::Warn(format!("kill switch probe failed: {}", probe.message));
+                    }
+                    _ => {}
+                }
+                if let Some(ref error) = kill_switch.error {
+                    return Check::Warn(error.clone());
+                }
+                let mut passed = Vec::new();
+                if kill_switch.home_count > 0 {
+                    passed.push(format!("exit IP {} is not a home IP", exit_ip));
+                }
+                if let Some(ref probe) = kill_switch.probe {
+                    passed.push(format!(
+                        "kill switch held at {}",
+                        probe.checked_at.format("%Y-%m-%d %H:%M:%S UTC")
+                    ));
+                }
+                if passed.is_empty() {
+                    Check::Pass("kill switch probe has not run yet".to_string())
+                } else {
+                    Check::Pass(passed.join(", "))
+                }
+            }
+            RuleKind::Tunnel => {
+                let Some(ref tunnel) = info.tunnel else {
+                    return Check::Fail(
+                        "tunnel state not available, GLUETUN_API_URL is not set".to_string(),
+                    );
+                };
+                match tunnel.state {
+                    Some(TunnelState::Running) => Check::Pass("VPN tunnel is running".to_string()),
+                    Some(TunnelState::Unknown) => {
+                        Check::Warn("Gluetun reported an unknown tunnel state".to_string())
+                    }
+                    // Stopped on purpose by the kill switch probe, which reports its own outcome
+                    Some(state) if tunnel.probing => Check::Warn(format!(
+                        "VPN tunnel is {} during the kill switch probe",
+                        state
+                    )),
+                    Some(TunnelState::Starting) => {
+                        Check::Warn("VPN tunnel is starting".to_string())
+                    }
+                    // A cached lookup can still show the exit IP from before the tunnel went down
+                    Some(state) => match (info.ip.as_deref(), info.cache_age_secs) {
+                        (Some(ip), Some(age)) => Check::Fail(format!(
+                            "VPN tunnel is {}, exit IP {} is from a lookup {}s ago",
+                            state, ip, age
+                        )),
+                        _ => Check::Fail(format!("VPN tunnel is {}", state)),
+                    },
+                    None => Check::Warn(format!(
+                        "tunnel state unknown: {}",
+                        tunnel.error.as_deref().unwrap_or("unknown error")
+                    )),
+                }
+            }
+            RuleKind::Consensus => {
+                let Some(ref consensus) = info.consensus else {
+                    return Check::Fail("consensus mode not enabled".to_string());
+                };
+                if !consensus.agreed {
+                    return Check::Fail(format!(
+                        "providers disagree: {}",
+                        consensus.disagreements.join("; ")
+                    ));
+                }
+                let answered = consensus
+                    .answers
+                    .iter()
+                    .filter(|a| a.error.is_none())
+                    .count();
+                if answered < 2 {
+                    Check::Warn(format!(
+                        "only {} of {} providers answered, nothing to compare",
+                        answered,
+                        consensus.answers.len()
+                    ))
+                } else {
+                    Check::Pass(format!("{} providers agree on IP and ASN", answered))
+                }
+            }
+        }
+    }
+}
+
+impl PolicyReport {
+    /// True unless a rule failed; warnings still pass
+    pub fn passed(&self) -> bool {
+        self.status != RuleStatus::Fail
+    }
+
+    /// Messages of the rules at the worst status, `None` when everything passed
+    pub fn reason(&self) -> Option<String> {
+        if self.status < RuleStatus::Warn {
+            return None;
+        }
+        let messages: Vec<&str> = self
+            .results
+            .iter()
+            .filter(|r| r.status == self.status)
+            .map(|r| r.message.as_str())
+            .collect();
+        Some(messages.join("; "))
+    }
+}
+
+/// Raw outcome of a check, before the rule's severity is applied
+enum Check {
+    Pass(String),
+    Warn(String),
+    Fail(String),
+    Skip,
+}
@@ -0,0 +1,100 @@
+// Allowlist module tests
+use gluetun_monitor::allowlist::{AllowList, AllowlistOverrides, AsnDiff, AsnVerdict};
+use std::{collections::HashSet, sync::Arc};
+
+fn set(asns: &[&str]) -> HashSet<String> {
+    asns.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_allowlist_replace_returns_diff() {
+    let allowlist = AllowList::new(set(&["AS1", "AS2"]));
+
+    let diff = allowlist.replace(set(&["AS2", "AS3", "AS4"]));
+
+    assert_eq!(diff.added, vec!["AS3", "AS4"]);
+    assert_eq!(diff.removed, vec!["AS1"]);
+    assert_eq!(diff.to_string(), "added AS3, AS4; removed AS1");
+    assert_eq!(allowlist.sorted(), vec!["AS2", "AS3", "AS4"]);
+}
+
+#[test]
+fn test_allowlist_snapshot_is_stable() {
+    let allowlist = AllowList::new(set(&["AS1"]));
+    let snapshot = allowlist.snapshot();
+
+    allowlist.replace(set(&["AS2"]));
+
+    // A snapshot taken before the swap is unaffected
+    assert!(snapshot.contains("AS1"));
+    assert!(!allowlist.contains("AS1"));
+    assert!(allowlist.contains("AS2"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_allowlist_concurrent_changes_persisted_in_order() {
+    let dir = std::env::temp_dir().join(format!("allowlist-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("state.json");
+    let allowlist = Arc::new(AllowList::new(set(&["AS1"])));
+
+    let handles: Vec<_> = (2..22)
+        .map(|n| {
+            let allowlist = allowlist.clone();
+            let path = path.clone();
+            tokio::spawn(async move {
+                let asn = format!("AS{}", n);
+                allowlist
+                    .add(std::slice::from_ref(&asn), Some(&path))
+                    .await
+                    .unwrap();
+                if n % 2 == 0 {
+                    allowlist.remove(&asn, Some(&path)).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    // The file holds the last change, not an older snapshot written late
+    let saved = AllowlistOverrides::load(&path).unwrap();
+    assert_eq!(saved, allowlist.overrides());
+    assert_eq!(saved.added.len(), 10);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_asn_diff_empty() {
+    let diff = AsnDiff::between(&set(&["AS1"]), &set(&["AS1"]));
+    assert!(diff.is_empty());
+    assert_eq!(diff.to_string(), "no changes");
+    assert!(AllowList::new(HashSet::new()).is_empty());
+}
+
+#[test]
+fn test_asn_verdict() {
+    let allowed = set(&["AS1"]);
+    let denied = set(&["AS7922"]);
+
+    assert_eq!(
+        AsnVerdict::of(Some("AS1"), &allowed, &denied),
+        AsnVerdict::Allowed
+    );
+    assert_eq!(
+        AsnVerdict::of(Some("AS7922"), &allowed, &denied),
+        AsnVerdict::Denied
+    );
+    assert_eq!(
+        AsnVerdict::of(Some("AS2"), &allowed, &denied),
+        AsnVerdict::Unknown
+    );
+    assert_eq!(AsnVerdict::of(None, &allowed, &denied), AsnVerdict::Unknown);
+
+    // The deny-list wins if an ASN is somehow in both
+    assert_eq!(
+        AsnVerdict::of(Some("AS7922"), &set(&["AS7922"]), &denied),
+        AsnVerdict::Denied
+    );
+}
@@ -0,0 +1,81 @@
+// CLI module tests
+use gluetun_monitor::cli::{Cli, Command};
+use std::path::PathBuf;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_cli_no_args() {
+    let cli = Cli::parse_from(args(&[])).unwrap();
+    assert_eq!(cli, Cli::default());
+}
+
+#[test]
+fn test_cli_config_path() {
+    let cli = Cli::parse_from(args(&["--config", "/etc/monitor.toml"])).unwrap();
+    assert_eq!(cli.config_path, Some(PathBuf::from("/etc/monitor.toml")));
+
+    let cli = Cli::parse_from(args(&["--config=/etc/monitor.yaml"])).unwrap();
+    assert_eq!(cli.config_path, Some(PathBuf::from("/etc/monitor.yaml")));
+}
+
+#[test]
+fn test_cli_errors() {
+    assert!(Cli::parse_from(args(&["--config"])).is_err());
+    assert!(Cli::parse_from(args(&["--bogus"])).is_err());
+}
+
+#[test]
+fn test_cli_check_config() {
+    let cli = Cli::parse_from(args(&["--check-config", "--config", "monitor.toml"])).unwrap();
+    assert!(cli.check_config);
+    assert_eq!(cli.config_path, Some(PathBuf::from("monitor.toml")));
+}
+
+#[test]
+fn test_cli_help() {
+    assert!(Cli::parse_from(args(&["-h"])).unwrap().help);
+}
+
+#[test]
+fn test_cli_discover_asns() {
+    let cli = Cli::parse_from(args(&["discover-asns"])).unwrap();
+    assert_eq!(
+        cli.command,
+        Command::DiscoverAsns {
+            iterations: 20,
+            timeout_secs: 300
+        }
+    );
+
+    let cli = Cli::parse_from(args(&[
+        "--config",
+        "monitor.toml",
+        "discover-asns",
+        "--iterations",
+        "5",
+        "--timeout",
+        "60",
+    ]))
+    .unwrap();
+    assert_eq!(cli.config_path, Some(PathBuf::from("monitor.toml")));
+    assert_eq!(
+        cli.command,
+        Command::DiscoverAsns {
+            iterations: 5,
+            timeout_secs: 60
+        }
+    );
+}
+
+#[test]
+fn test_cli_discover_asns_errors() {
+    // Discovery options are only valid after the command
+    assert!(Cli::parse_from(args(&["--iterations", "5"])).is_err());
+    assert!(Cli::parse_from(args(&["discover-asns", "--iterations", "many"])).is_err());
+    assert!(Cli::parse_from(args(&["discover-asns", "--iterations", "0"])).is_err());
+    assert!(Cli::parse_from(args(&["discover-asns", "--timeout"])).is_err());
+    assert!(Cli::parse_from(args(&["discover-asns", "discover-asns"])).is_err());
+}
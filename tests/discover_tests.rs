@@ -0,0 +1,183 @@
+// ASN discovery tests against a mock Gluetun control server
+use gluetun_monitor::discover::{allowed_asns_line, reconnect, run, DiscoverOptions};
+use mockito::{Matcher, Server, ServerGuard};
+use std::{collections::BTreeMap, time::Duration};
+
+fn options(iterations: u32) -> DiscoverOptions {
+    DiscoverOptions {
+        iterations,
+        wait_timeout: Duration::from_millis(200),
+        poll_interval: Duration::from_millis(10),
+    }
+}
+
+fn public_ip(ip: &str, organization: &str) -> String {
+    format!(
+        r#"{{"public_ip":"{}","country":"Netherlands","organization":"{}"}}"#,
+        ip, organization
+    )
+}
+
+async fn mock_vpn_status(server: &mut ServerGuard, status: &str) -> mockito::Mock {
+    server
+        .mock("PUT", "/v1/vpn/status")
+        .match_header("x-api-key", "secret")
+        .match_body(Matcher::Json(serde_json::json!({ "status": status })))
+        .with_body(format!(r#"{{"outcome":"{}"}}"#, status))
+        .create_async()
+        .await
+}
+
+#[tokio::test]
+async fn test_reconnect_waits_for_new_ip() {
+    let mut server = Server::new_async().await;
+    let stopped = mock_vpn_status(&mut server, "stopped").await;
+    let running = mock_vpn_status(&mut server, "running").await;
+    // Before the reconnect and on the first poll Gluetun still reports the old IP
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("1.1.1.1", "AS1111 Old Exit"))
+        .expect(2)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("2.2.2.2", "AS2222 New Exit"))
+        .create_async()
+        .await;
+
+    let result = reconnect(
+        &reqwest::Client::new(),
+        &server.url(),
+        Some("secret"),
+        &options(1),
+    )
+    .await
+    .unwrap();
+
+    stopped.assert_async().await;
+    running.assert_async().await;
+    assert!(result.ip_changed);
+    assert_eq!(result.lookup.ip, Some("2.2.2.2".to_string()));
+    assert_eq!(result.lookup.asn, Some("AS2222".to_string()));
+}
+
+#[tokio::test]
+async fn test_reconnect_same_ip_after_timeout() {
+    let mut server = Server::new_async().await;
+    mock_vpn_status(&mut server, "stopped").await;
+    mock_vpn_status(&mut server, "running").await;
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("1.1.1.1", "AS1111 Same Exit"))
+        .create_async()
+        .await;
+
+    let result = reconnect(
+        &reqwest::Client::new(),
+        &server.url(),
+        Some("secret"),
+        &options(1),
+    )
+    .await
+    .unwrap();
+
+    // The ASN is still reported, flagged as unchanged
+    assert!(!result.ip_changed);
+    assert_eq!(result.lookup.asn, Some("AS1111".to_string()));
+}
+
+#[tokio::test]
+async fn test_reconnect_same_ip_once_running() {
+    let mut server = Server::new_async().await;
+    mock_vpn_status(&mut server, "stopped").await;
+    mock_vpn_status(&mut server, "running").await;
+    server
+        .mock("GET", "/v1/vpn/status")
+        .with_body(r#"{"status":"running"}"#)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("1.1.1.1", "AS1111 Same Exit"))
+        .create_async()
+        .await;
+
+    // Accepted without waiting out the timeout
+    let options = DiscoverOptions {
+        wait_timeout: Duration::from_secs(300),
+        ..options(1)
+    };
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        reconnect(
+            &reqwest::Client::new(),
+            &server.url(),
+            Some("secret"),
+            &options,
+        ),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert!(!result.ip_changed);
+    assert_eq!(result.lookup.ip, Some("1.1.1.1".to_string()));
+    assert_eq!(result.lookup.asn, Some("AS1111".to_string()));
+}
+
+#[tokio::test]
+async fn test_reconnect_fails_when_tunnel_cannot_be_toggled() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("PUT", "/v1/vpn/status")
+        .with_status(401)
+        .create_async()
+        .await;
+
+    let err = reconnect(&reqwest::Client::new(), &server.url(), None, &options(1))
+        .await
+        .unwrap_err();
+
+    assert!(err.contains("failed to stop the VPN"));
+    assert!(err.contains("401"));
+}
+
+#[tokio::test]
+async fn test_run_collects_distinct_asns() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("PUT", "/v1/vpn/status")
+        .expect(4)
+        .create_async()
+        .await;
+    // Initial IP, then a new exit for each of the two reconnects
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("1.1.1.1", "AS1111 First"))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("2.2.2.2", "AS2222 Second"))
+        .expect(2)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip("3.3.3.3", "AS1111 First"))
+        .create_async()
+        .await;
+
+    let asns = run(&reqwest::Client::new(), &server.url(), None, &options(2)).await;
+
+    assert_eq!(asns.len(), 2);
+    assert_eq!(asns.get("AS2222"), Some(&Some("Second".to_string())));
+    assert_eq!(allowed_asns_line(&asns), "VPN_ALLOWED_ASNS=AS1111,AS2222");
+}
+
+#[test]
+fn test_allowed_asns_line_empty() {
+    assert_eq!(allowed_asns_line(&BTreeMap::new()), "VPN_ALLOWED_ASNS=");
+}
@@ -0,0 +1,98 @@
+// DNS module tests
+use gluetun_monitor::dns::{parse_server, DnsProbe};
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::net::UdpSocket;
+
+/// Stand-in DNS server answering A queries for `whoami` with `resolver`,
+/// NXDOMAIN for `missing.test`, a truncated response for `truncated.test`
+/// and no records for everything else
+async fn dns_server(resolver: Ipv4Addr) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 512];
+        loop {
+            let Ok((len, peer)) = socket.recv_from(&mut buf).await else {
+                return;
+            };
+            let query = &buf[..len];
+            // Question name ends at the first zero label, then type and class
+            let name_end = 12 + query[12..].iter().position(|&b| b == 0).unwrap() + 1;
+            let qtype = u16::from_be_bytes([query[name_end], query[name_end + 1]]);
+            let question = &query[12..name_end + 4];
+            let missing = question.windows(7).any(|w| w == b"missing");
+            let truncated = question.windows(9).any(|w| w == b"truncated");
+
+            let mut response = query[..2].to_vec();
+            response.extend_from_slice(&[
+                if truncated { 0x83 } else { 0x81 },
+                if missing { 0x83 } else { 0x80 },
+            ]);
+            let answers = u16::from(!missing && qtype == 1 && question.starts_with(b"\x06whoami"));
+            response.extend_from_slice(&[0, 1]);
+            response.extend_from_slice(&answers.to_be_bytes());
+            response.extend_from_slice(&[0, 0, 0, 0]);
+            response.extend_from_slice(question);
+            if answers == 1 {
+                // Pointer to the question name, A, IN, TTL 60, 4 bytes
+                response.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+                response.extend_from_slice(&resolver.octets());
+            }
+            let _ = socket.send_to(&response, peer).await;
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn test_probe_reports_resolver() {
+    let server = dns_server(Ipv4Addr::new(10, 64, 0, 1)).await;
+    let probe = DnsProbe::new(
+        "whoami.example.net",
+        vec!["missing.test".to_string(), "truncated.test".to_string()],
+        Some(server),
+        None,
+    );
+
+    let report = probe.run().await;
+    assert_eq!(report.error, None);
+    assert_eq!(report.server, Some(server.to_string()));
+    assert_eq!(report.resolver_ips(), vec!["10.64.0.1"]);
+    assert_eq!(report.resolvers[0].asn, None);
+    assert_eq!(report.names.len(), 2);
+    assert_eq!(report.names[0].error.as_deref(), Some("NXDOMAIN"));
+    assert_eq!(
+        report.names[1].error.as_deref(),
+        Some("DNS response truncated")
+    );
+}
+
+#[tokio::test]
+async fn test_probe_whoami_without_answer() {
+    let server = dns_server(Ipv4Addr::new(10, 64, 0, 1)).await;
+    let probe = DnsProbe::new("resolver.example.net", Vec::new(), Some(server), None);
+
+    let report = probe.run().await;
+    assert!(report.resolvers.is_empty());
+    assert_eq!(
+        report.error.as_deref(),
+        Some("resolver.example.net: no addresses")
+    );
+}
+
+#[test]
+fn test_parse_server() {
+    assert_eq!(
+        parse_server("10.64.0.1").unwrap(),
+        "10.64.0.1:53".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(
+        parse_server("127.0.0.1:5353").unwrap(),
+        "127.0.0.1:5353".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(
+        parse_server("[::1]").unwrap(),
+        "[::1]:53".parse::<SocketAddr>().unwrap()
+    );
+    assert!(parse_server("dns.example.com").is_err());
+}
@@ -0,0 +1,116 @@
+// Geofence module tests
+use gluetun_monitor::{
+    country::country_code,
+    geofence::{Geofence, LocationRule},
+    models::LookupResult,
+};
+
+fn location(country: Option<&str>, region: Option<&str>, city: Option<&str>) -> LookupResult {
+    serde_json::from_value(serde_json::json!({
+        "ip": "1.2.3.4",
+        "asn": "AS12345",
+        "country": country,
+        "region": region,
+        "city": city,
+    }))
+    .unwrap()
+}
+
+fn names(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_geofence_empty_allows_everything() {
+    let geofence = Geofence::default();
+    assert!(geofence.is_empty());
+    assert!(geofence.violations(&location(None, None, None)).is_empty());
+}
+
+#[test]
+fn test_geofence_allowed_countries() {
+    let geofence = Geofence {
+        countries: LocationRule::countries(names(&["Netherlands", " ch "]), names(&[])),
+        ..Geofence::default()
+    };
+
+    // Case-insensitive, ISO codes work if the provider reports them
+    assert!(geofence
+        .violations(&location(Some("netherlands"), None, None))
+        .is_empty());
+    assert!(geofence
+        .violations(&location(Some("CH"), None, None))
+        .is_empty());
+    assert_eq!(
+        geofence.violations(&location(Some("Germany"), None, None)),
+        vec!["country 'Germany' is not in VPN_ALLOWED_COUNTRIES"]
+    );
+
+    // Fails closed when the provider does not report a country
+    assert_eq!(
+        geofence.violations(&location(None, None, None)),
+        vec!["country unknown, required by VPN_ALLOWED_COUNTRIES"]
+    );
+}
+
+#[test]
+fn test_geofence_countries_match_any_provider() {
+    let geofence = Geofence {
+        countries: LocationRule::countries(names(&["nl", "The Netherlands"]), names(&["US"])),
+        ..Geofence::default()
+    };
+
+    // ipinfo.io answers codes, Gluetun and ifconfig.co answer names
+    let ipinfo: LookupResult = serde_json::from_value(serde_json::json!({
+        "ip": "1.2.3.4",
+        "asn": "AS12345",
+        "country": "NL",
+        "source": "ipinfo",
+    }))
+    .unwrap();
+    assert!(geofence.violations(&ipinfo).is_empty());
+    assert!(geofence
+        .violations(&location(Some("Netherlands"), None, None))
+        .is_empty());
+    assert_eq!(
+        geofence.violations(&location(Some("United States of America"), None, None)),
+        vec!["country 'United States of America' is in VPN_DENIED_COUNTRIES"]
+    );
+}
+
+#[test]
+fn test_country_code() {
+    assert_eq!(country_code("Netherlands"), Some("NL"));
+    assert_eq!(country_code(" the netherlands "), Some("NL"));
+    assert_eq!(country_code("ch"), Some("CH"));
+    assert_eq!(country_code("Korea, Republic of"), Some("KR"));
+    assert_eq!(country_code("Czech Republic"), Some("CZ"));
+    assert_eq!(country_code("Narnia"), None);
+}
+
+#[test]
+fn test_geofence_denied_and_multiple_levels() {
+    let geofence = Geofence {
+        countries: LocationRule::countries(names(&[]), names(&["United States"])),
+        regions: LocationRule::new(names(&[]), names(&["California"])),
+        cities: LocationRule::new(names(&["Zurich"]), names(&[])),
+    };
+
+    // Denied lists do not require the level to be reported
+    assert!(geofence
+        .violations(&location(Some("Switzerland"), None, Some("Zurich")))
+        .is_empty());
+
+    assert_eq!(
+        geofence.violations(&location(
+            Some("United States"),
+            Some("California"),
+            Some("Los Angeles")
+        )),
+        vec![
+            "country 'United States' is in VPN_DENIED_COUNTRIES",
+            "region 'California' is in VPN_DENIED_REGIONS",
+            "city 'Los Angeles' is not in VPN_ALLOWED_CITIES",
+        ]
+    );
+}
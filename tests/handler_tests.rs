@@ -3,11 +3,31 @@
 // These tests verify the HTTP handlers work correctly by calling them directly.
 // Since handlers return `impl IntoResponse`, we test that they execute without panicking.
 
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use gluetun_monitor::{
-    handlers::{check_handler, status_handler},
-    models::AppState,
+    allowlist::AllowList,
+    geofence::{Geofence, LocationRule},
+    handlers::{
+        admin_add_handler, admin_list_handler, admin_promote_handler, admin_remove_handler,
+        check_handler, config_handler, metrics_handler, observed_asns_handler, status_handler,
+    },
+    ip_lookup::{build_providers, LookupCache},
+    models::{
+        AdminAsnsResponse, AppState, AsnRequest, LookupQuery, ObservedAsnsResponse, PromoteRequest,
+    },
+    monitoring::AsnObserver,
+    policy::Policy,
+};
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+    time::Duration,
 };
-use std::{collections::HashSet, sync::Arc};
 
 fn create_test_state() -> AppState {
     let mut allowed_asns = HashSet::new();
@@ -15,21 +35,35 @@ fn create_test_state() -> AppState {
     allowed_asns.insert("AS67890".to_string());
 
     AppState {
-        allowed_asns: Arc::new(allowed_asns),
+        allowed_asns: Arc::new(AllowList::new(allowed_asns)),
+        policy: Arc::new(Policy::default()),
         client: reqwest::Client::new(),
-        ntfy_url: Some("https://ntfy.sh/test".to_string()),
+        ntfy_urls: Arc::new(vec!["https://ntfy.sh/test".to_string()]),
         gluetun_url: None,
         gluetun_api_key: None,
+        providers: Arc::new(Vec::new()),
+        lookup_cache: Arc::new(LookupCache::new(Duration::from_secs(30))),
+        effective_config: Arc::new(RwLock::new(serde_json::json!({}))),
+        admin_token: None,
+        admin_state_file: None,
+        asn_observer: None,
     }
 }
 
 fn create_unconfigured_state() -> AppState {
     AppState {
-        allowed_asns: Arc::new(HashSet::new()),
+        allowed_asns: Arc::new(AllowList::new(HashSet::new())),
+        policy: Arc::new(Policy::default()),
         client: reqwest::Client::new(),
-        ntfy_url: None,
+        ntfy_urls: Arc::new(Vec::new()),
         gluetun_url: None,
         gluetun_api_key: None,
+        providers: Arc::new(Vec::new()),
+        lookup_cache: Arc::new(LookupCache::new(Duration::from_secs(30))),
+        effective_config: Arc::new(RwLock::new(serde_json::json!({}))),
+        admin_token: None,
+        admin_state_file: None,
+        asn_observer: None,
     }
 }
 
@@ -37,7 +71,7 @@ fn create_unconfigured_state() -> AppState {
 async fn test_status_handler_executes() {
     let state = create_test_state();
     // Handler should execute without panicking
-    let _response = status_handler(axum::extract::State(state)).await;
+    let _response = status_handler(State(state), Query(LookupQuery::default())).await;
     // If we get here, the handler executed successfully
 }
 
@@ -45,21 +79,33 @@ async fn test_status_handler_executes() {
 async fn test_status_handler_unconfigured_executes() {
     let state = create_unconfigured_state();
     // Handler should execute even when unconfigured
-    let _response = status_handler(axum::extract::State(state)).await;
+    let _response = status_handler(State(state), Query(LookupQuery::default())).await;
 }
 
 #[tokio::test]
 async fn test_check_handler_no_asns_executes() {
     let state = create_unconfigured_state();
     // Handler should execute and return proper error response
-    let _response = check_handler(axum::extract::State(state)).await;
+    let _response = check_handler(State(state), Query(LookupQuery::default())).await;
 }
 
 #[tokio::test]
 async fn test_check_handler_with_configuration_executes() {
     let state = create_test_state();
     // Handler should execute (will fail IP lookup but shouldn't panic)
-    let _response = check_handler(axum::extract::State(state)).await;
+    let _response = check_handler(State(state), Query(LookupQuery::default())).await;
+}
+
+#[tokio::test]
+async fn test_metrics_handler_executes() {
+    let state = create_test_state();
+    let _response = metrics_handler(State(state)).await;
+}
+
+#[tokio::test]
+async fn test_config_handler_executes() {
+    let state = create_test_state();
+    let _response = config_handler(State(state)).await;
 }
 
 #[test]
@@ -69,14 +115,401 @@ fn test_app_state_creation() {
     allowed_asns.insert("AS12345".to_string());
 
     let state = AppState {
-        allowed_asns: Arc::new(allowed_asns),
+        allowed_asns: Arc::new(AllowList::new(allowed_asns)),
+        policy: Arc::new(Policy::default()),
         client: reqwest::Client::new(),
-        ntfy_url: Some("https://ntfy.sh/test".to_string()),
+        ntfy_urls: Arc::new(vec!["https://ntfy.sh/test".to_string()]),
         gluetun_url: Some("http://localhost:8000".to_string()),
         gluetun_api_key: Some("test-key".to_string()),
+        providers: Arc::new(Vec::new()),
+        lookup_cache: Arc::new(LookupCache::new(Duration::from_secs(30))),
+        effective_config: Arc::new(RwLock::new(serde_json::json!({}))),
+        admin_token: None,
+        admin_state_file: None,
+        asn_observer: None,
     };
 
-    assert_eq!(state.allowed_asns.len(), 1);
-    assert!(state.ntfy_url.is_some());
+    assert_eq!(state.allowed_asns.sorted(), vec!["AS12345".to_string()]);
+    assert!(!state.ntfy_urls.is_empty());
     assert!(state.gluetun_url.is_some());
 }
+
+#[test]
+fn test_lookup_query_fresh() {
+    let query = |v: Option<&str>| LookupQuery {
+        fresh: v.map(str::to_string),
+    };
+
+    assert!(!query(None).is_fresh());
+    assert!(query(Some("1")).is_fresh());
+    assert!(query(Some("TRUE")).is_fresh());
+    assert!(!query(Some("0")).is_fresh());
+}
+
+fn create_admin_state() -> AppState {
+    let mut state = create_test_state();
+    state.admin_token = Some("test-admin-token-1234".to_string());
+    state
+}
+
+fn bearer(token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    headers
+}
+
+fn asn_request(asn: &str) -> Json<AsnRequest> {
+    Json(AsnRequest {
+        asn: asn.to_string(),
+    })
+}
+
+async fn admin_body(response: Response) -> AdminAsnsResponse {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_admin_disabled_without_token() {
+    let response = admin_list_handler(State(create_test_state()), bearer("anything")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_rejects_invalid_token() {
+    let state = create_admin_state();
+
+    let response = admin_list_handler(State(state.clone()), HeaderMap::new()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response =
+        admin_add_handler(State(state.clone()), bearer("wrong"), asn_request("AS1")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(!state.allowed_asns.contains("AS1"));
+}
+
+#[tokio::test]
+async fn test_admin_add_and_remove_asns() {
+    let state = create_admin_state();
+    let token = bearer("test-admin-token-1234");
+
+    let response =
+        admin_add_handler(State(state.clone()), token.clone(), asn_request("as11111")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = admin_body(response).await;
+    assert_eq!(body.allowed_asns, vec!["AS11111", "AS12345", "AS67890"]);
+    assert_eq!(body.added, vec!["AS11111"]);
+    assert!(!body.persisted);
+
+    // Removing a configured ASN is recorded as an override
+    let response =
+        admin_remove_handler(State(state.clone()), token.clone(), asn_request("AS12345")).await;
+    let body = admin_body(response).await;
+    assert_eq!(body.allowed_asns, vec!["AS11111", "AS67890"]);
+    assert_eq!(body.removed, vec!["AS12345"]);
+    assert!(!state.allowed_asns.contains("AS12345"));
+
+    // Overrides survive a reload of the configured list
+    state.allowed_asns.replace(HashSet::from([
+        "AS12345".to_string(),
+        "AS22222".to_string(),
+    ]));
+    assert_eq!(state.allowed_asns.sorted(), vec!["AS11111", "AS22222"]);
+
+    let response = admin_add_handler(State(state), token, asn_request("12345")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_admin_persists_overrides() {
+    let path =
+        std::env::temp_dir().join(format!("gluetun-monitor-admin-{}.json", std::process::id()));
+    let mut state = create_admin_state();
+    state.admin_state_file = Some(path.clone());
+
+    let response = admin_add_handler(
+        State(state),
+        bearer("test-admin-token-1234"),
+        asn_request("AS11111"),
+    )
+    .await;
+    assert!(admin_body(response).await.persisted);
+
+    // A restart picks the change up again
+    let overrides = gluetun_monitor::allowlist::AllowlistOverrides::load(&path).unwrap();
+    let restored = AllowList::with_overrides(HashSet::from(["AS12345".to_string()]), overrides);
+    assert_eq!(restored.sorted(), vec!["AS11111", "AS12345"]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+fn create_learning_state(asns: &[&str]) -> AppState {
+    let observer = AsnObserver::new(24);
+    for asn in asns {
+        let info = serde_json::from_value(serde_json::json!({
+            "asn": asn,
+            "org": "Observed Org",
+            "checked_at": chrono::Utc::now(),
+        }))
+        .unwrap();
+        observer.record(&info);
+    }
+
+    let mut state = create_admin_state();
+    state.asn_observer = Some(Arc::new(observer));
+    state
+}
+
+#[tokio::test]
+async fn test_observed_asns_handler() {
+    let response = observed_asns_handler(State(create_test_state())).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let state = create_learning_state(&["AS12345", "AS11111", "AS11111"]);
+    let response = observed_asns_handler(State(state)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: ObservedAsnsResponse = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body.window_hours, 24);
+    assert_eq!(body.observed.len(), 2);
+    assert_eq!(body.observed[0].observed.asn, "AS11111");
+    assert_eq!(body.observed[0].observed.count, 2);
+    assert!(!body.observed[0].allowed);
+    assert!(body.observed[1].allowed);
+}
+
+#[tokio::test]
+async fn test_admin_promote_observed_asns() {
+    let state = create_learning_state(&["AS11111", "AS22222", "NOTANASN"]);
+    let token = bearer("test-admin-token-1234");
+
+    // Only observed ASNs can be promoted
+    let response = admin_promote_handler(
+        State(state.clone()),
+        token.clone(),
+        Some(Json(PromoteRequest {
+            asns: vec!["AS33333".to_string()],
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = admin_promote_handler(
+        State(state.clone()),
+        token.clone(),
+        Some(Json(PromoteRequest {
+            asns: vec!["as11111".to_string()],
+        })),
+    )
+    .await;
+    assert_eq!(admin_body(response).await.added, vec!["AS11111"]);
+
+    // Without a body every valid observed ASN is promoted
+    let response = admin_promote_handler(State(state.clone()), token, None).await;
+    let body = admin_body(response).await;
+    assert_eq!(body.added, vec!["AS11111", "AS22222"]);
+    assert!(!state.allowed_asns.contains("NOTANASN"));
+}
+
+#[tokio::test]
+async fn test_admin_rejects_denied_asns() {
+    let mut state = create_learning_state(&["AS11111", "AS7922"]);
+    state.policy = Arc::new(Policy {
+        denied_asns: HashSet::from(["AS7922".to_string()]),
+        ..Default::default()
+    });
+    let token = bearer("test-admin-token-1234");
+
+    let response =
+        admin_add_handler(State(state.clone()), token.clone(), asn_request("as7922")).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = admin_promote_handler(
+        State(state.clone()),
+        token.clone(),
+        Some(Json(PromoteRequest {
+            asns: vec!["AS7922".to_string()],
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Promoting everything observed leaves the home ISP ASN out
+    let response = admin_promote_handler(State(state.clone()), token, None).await;
+    assert_eq!(admin_body(response).await.added, vec!["AS11111"]);
+    assert!(!state.allowed_asns.contains("AS7922"));
+}
+
+/// State whose only provider is a mock echo service reporting `asn`
+async fn create_mock_state(server: &mut mockito::ServerGuard, asn: &str) -> AppState {
+    let body = serde_json::json!({ "ip": "1.2.3.4", "asn": asn });
+    create_mock_state_with(server, body).await
+}
+
+/// State whose only provider is a mock echo service answering `body`
+async fn create_mock_state_with(
+    server: &mut mockito::ServerGuard,
+    body: serde_json::Value,
+) -> AppState {
+    server
+        .mock("GET", "/json")
+        .with_body(body.to_string())
+        .create_async()
+        .await;
+    let providers = build_providers(
+        &[format!("ifconfig={}/json", server.url())],
+        None,
+        None,
+        &std::collections::HashMap::new(),
+        None,
+    )
+    .unwrap();
+
+    let mut state = create_test_state();
+    state.providers = Arc::new(providers);
+    state.policy = Arc::new(Policy::new(
+        HashSet::from(["AS7922".to_string()]),
+        Geofence::default(),
+        Vec::new(),
+    ));
+    state
+}
+
+async fn check_body(state: AppState) -> (StatusCode, serde_json::Value) {
+    let response = check_handler(State(state), Query(LookupQuery::default()))
+        .await
+        .into_response();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+async fn check_reason(state: AppState) -> (StatusCode, Option<String>) {
+    let (status, body) = check_body(state).await;
+    (status, body["reason"].as_str().map(str::to_string))
+}
+
+#[tokio::test]
+async fn test_check_handler_reasons() {
+    let mut server = mockito::Server::new_async().await;
+    let state = create_mock_state(&mut server, "AS12345").await;
+    assert_eq!(check_reason(state).await, (StatusCode::OK, None));
+
+    let mut server = mockito::Server::new_async().await;
+    let state = create_mock_state(&mut server, "AS99999").await;
+    assert_eq!(
+        check_reason(state).await,
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("ASN not allowed".to_string())
+        )
+    );
+
+    let mut server = mockito::Server::new_async().await;
+    let state = create_mock_state(&mut server, "AS7922").await;
+    assert_eq!(
+        check_reason(state).await,
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("traffic leaking via home ISP; ASN not allowed".to_string())
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_check_handler_geofence() {
+    let mut server = mockito::Server::new_async().await;
+    let body = serde_json::json!({ "ip": "1.2.3.4", "asn": "AS99999", "country": "Germany" });
+    let mut state = create_mock_state_with(&mut server, body).await;
+    state.policy = Arc::new(Policy::new(
+        HashSet::new(),
+        Geofence {
+            countries: LocationRule::countries(vec!["Netherlands".to_string()], Vec::new()),
+            ..Geofence::default()
+        },
+        Vec::new(),
+    ));
+
+    // Every violated rule is named
+    assert_eq!(
+        check_reason(state).await,
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("ASN not allowed; country 'Germany' is not in VPN_ALLOWED_COUNTRIES".to_string())
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_check_handler_reports_every_rule() {
+    let mut server = mockito::Server::new_async().await;
+    let body = serde_json::json!({ "ip": "1.2.3.4", "asn": "AS12345" });
+    let mut state = create_mock_state_with(&mut server, body).await;
+    let rules = ["port_forward:warn", "asn_allow", "gluetun_source"]
+        .iter()
+        .map(|spec| spec.parse().unwrap())
+        .collect();
+    state.policy = Arc::new(Policy::default().with_rules(rules));
+
+    let (status, body) = check_body(state).await;
+
+    // gluetun_source fails: the answer came from the ifconfig mock
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "fail");
+    assert_eq!(body["reason"], "answered by ifconfig instead of Gluetun");
+    let statuses: Vec<(&str, &str)> = body["rules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["rule"].as_str().unwrap(), r["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("lookup", "pass"),
+            ("port_forward", "warn"),
+            ("asn_allow", "pass"),
+            ("gluetun_source", "fail"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_check_handler_warnings_pass() {
+    let mut server = mockito::Server::new_async().await;
+    let mut state = create_mock_state(&mut server, "AS12345").await;
+    let policy = Policy::default();
+    let mut rules = policy.rules.clone();
+    rules.push("port_forward:warn".parse().unwrap());
+    state.policy = Arc::new(policy.with_rules(rules));
+
+    let (status, body) = check_body(state).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ok"], true);
+    assert_eq!(body["status"], "warn");
+    assert_eq!(body["reason"], "no forwarded port");
+}
+
+#[tokio::test]
+async fn test_check_handler_lookup_failure_skips_rules() {
+    // No providers, so the lookup fails
+    let (status, body) = check_body(create_test_state()).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "fail");
+    assert_eq!(body["rules"][0]["rule"], "lookup");
+    assert_eq!(body["rules"][0]["status"], "fail");
+    assert_eq!(body["rules"][1]["rule"], "asn_allow");
+    assert_eq!(body["rules"][1]["status"], "skip");
+}
@@ -0,0 +1,185 @@
+// Kill switch verification tests against a mock Gluetun control server
+use gluetun_monitor::{killswitch::KillSwitch, models::ProbeOutcome, policy::parse_cidr};
+use mockito::{Matcher, Server, ServerGuard};
+
+fn public_ip(ip: &str) -> String {
+    format!(
+        r#"{{"public_ip":"{}","country":"Netherlands","organization":"AS9009 M247"}}"#,
+        ip
+    )
+}
+
+async fn mock_public_ip(server: &mut ServerGuard, ip: &str) -> mockito::Mock {
+    server
+        .mock("GET", "/v1/publicip/ip")
+        .with_body(public_ip(ip))
+        .create_async()
+        .await
+}
+
+async fn mock_vpn_status(server: &mut ServerGuard, status: &str) -> mockito::Mock {
+    server
+        .mock("PUT", "/v1/vpn/status")
+        .match_body(Matcher::Json(serde_json::json!({ "status": status })))
+        .with_body(format!(r#"{{"outcome":"{}"}}"#, status))
+        .create_async()
+        .await
+}
+
+/// Gluetun reporting the tunnel as `state` on `GET /v1/vpn/status`
+async fn mock_vpn_state(server: &mut ServerGuard, state: &str) -> mockito::Mock {
+    server
+        .mock("GET", "/v1/vpn/status")
+        .with_body(format!(r#"{{"status":"{}"}}"#, state))
+        .create_async()
+        .await
+}
+
+#[tokio::test]
+async fn test_verify_exit_differs_from_home() {
+    let mut server = Server::new_async().await;
+    mock_public_ip(&mut server, "185.159.157.10").await;
+    let home = server
+        .mock("GET", "/home")
+        .with_body("203.0.113.7\n")
+        .create_async()
+        .await;
+    // Only the exit IP is needed, not the forwarded port
+    let port_forwarded = server
+        .mock("GET", "/v1/openvpn/portforwarded")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let kill_switch = KillSwitch::new(
+        &server.url(),
+        None,
+        vec![parse_cidr("2001:db8::/48").unwrap()],
+        Some(format!("{}/home", server.url())),
+    );
+    let status = kill_switch.verify(&reqwest::Client::new()).await;
+
+    home.assert_async().await;
+    port_forwarded.assert_async().await;
+    assert_eq!(status.exit_ip.as_deref(), Some("185.159.157.10"));
+    assert_eq!(status.home_count, 2);
+    assert!(!status.leaking);
+    assert_eq!(status.error, None);
+    assert_eq!(status.probe, None);
+}
+
+#[tokio::test]
+async fn test_verify_exit_is_home_ip() {
+    let mut server = Server::new_async().await;
+    mock_public_ip(&mut server, "203.0.113.7").await;
+    server
+        .mock("GET", "/home")
+        .with_status(502)
+        .create_async()
+        .await;
+
+    let kill_switch = KillSwitch::new(
+        &server.url(),
+        None,
+        vec![parse_cidr("203.0.113.0/24").unwrap()],
+        Some(format!("{}/home", server.url())),
+    );
+    let status = kill_switch.verify(&reqwest::Client::new()).await;
+
+    // The configured range still catches the leak when the home lookup fails
+    assert!(status.leaking);
+    assert_eq!(
+        status.error.as_deref(),
+        Some("home lookup failed: HTTP status 502")
+    );
+}
+
+#[tokio::test]
+async fn test_probe_kill_switch_holds() {
+    let mut server = Server::new_async().await;
+    let stopped = mock_vpn_status(&mut server, "stopped").await;
+    let running = mock_vpn_status(&mut server, "running").await;
+    let state = mock_vpn_state(&mut server, "stopped").await;
+    mock_public_ip(&mut server, "185.159.157.10").await;
+
+    // Nothing listens on the probe URL, as if the firewall dropped the request
+    let kill_switch = KillSwitch::new(&server.url(), None, Vec::new(), None)
+        .with_probe_url("http://127.0.0.1:1/");
+    let probe = kill_switch.probe().await;
+
+    stopped.assert_async().await;
+    state.assert_async().await;
+    running.assert_async().await;
+    assert_eq!(probe.outcome, ProbeOutcome::Held);
+    assert_eq!(
+        probe.message,
+        "http://127.0.0.1:1/ unreachable with the tunnel stopped"
+    );
+    assert_eq!(kill_switch.last_probe(), Some(probe));
+    assert!(!kill_switch.probing());
+}
+
+#[tokio::test]
+async fn test_probe_kill_switch_leaks() {
+    let mut server = Server::new_async().await;
+    mock_vpn_status(&mut server, "stopped").await;
+    let running = mock_vpn_status(&mut server, "running").await;
+    mock_vpn_state(&mut server, "stopped").await;
+    mock_public_ip(&mut server, "185.159.157.10").await;
+    server
+        .mock("GET", "/trace")
+        .with_body("ip=203.0.113.7")
+        .create_async()
+        .await;
+
+    let probe_url = format!("{}/trace", server.url());
+    let kill_switch =
+        KillSwitch::new(&server.url(), None, Vec::new(), None).with_probe_url(&probe_url);
+    let probe = kill_switch.probe().await;
+
+    // The tunnel is restarted even though the probe got out
+    running.assert_async().await;
+    assert_eq!(probe.outcome, ProbeOutcome::Leaked);
+    assert_eq!(
+        probe.message,
+        format!("{} answered HTTP 200 with the tunnel stopped", probe_url)
+    );
+}
+
+#[tokio::test]
+async fn test_probe_cannot_stop_tunnel() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("PUT", "/v1/vpn/status")
+        .with_status(401)
+        .create_async()
+        .await;
+
+    let kill_switch = KillSwitch::new(&server.url(), None, Vec::new(), None);
+    let probe = kill_switch.probe().await;
+
+    assert_eq!(probe.outcome, ProbeOutcome::Error);
+    assert_eq!(probe.message, "failed to stop the VPN: HTTP status 401");
+}
+
+#[tokio::test]
+async fn test_probe_failure_before_network_is_error() {
+    let mut server = Server::new_async().await;
+    mock_vpn_status(&mut server, "stopped").await;
+    let running = mock_vpn_status(&mut server, "running").await;
+    mock_vpn_state(&mut server, "stopped").await;
+    mock_public_ip(&mut server, "185.159.157.10").await;
+
+    // A failed TLS handshake with a plain HTTP server proves nothing
+    let probe_url = format!("{}/trace", server.url().replace("http://", "https://"));
+    let kill_switch =
+        KillSwitch::new(&server.url(), None, Vec::new(), None).with_probe_url(&probe_url);
+    let probe = kill_switch.probe().await;
+
+    running.assert_async().await;
+    assert_eq!(probe.outcome, ProbeOutcome::Error);
+    assert!(probe.message.starts_with(&format!(
+        "{} failed, but not because the connection was blocked",
+        probe_url
+    )));
+}
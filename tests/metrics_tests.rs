@@ -0,0 +1,61 @@
+// Metrics module tests
+use gluetun_monitor::{
+    allowlist::AsnVerdict,
+    metrics,
+    models::{LookupError, LookupResult},
+};
+use std::time::Duration;
+
+fn lookup_result(asn: &str, port: Option<u16>) -> LookupResult {
+    LookupResult {
+        ip: Some("1.2.3.4".to_string()),
+        asn: Some(asn.to_string()),
+        port_forwarded: port,
+        source: Some("gluetun".to_string()),
+        ..Default::default()
+    }
+}
+
+// Metrics are process-global, so all assertions live in one test
+#[test]
+fn test_metrics_render() {
+    metrics::record_status(
+        &lookup_result("AS12345", Some(54321)),
+        AsnVerdict::Allowed,
+        true,
+    );
+    metrics::observe_lookup("gluetun", Duration::from_millis(120));
+    metrics::lookup_failed("ifconfig", &LookupError::HttpStatus { status: 429 });
+    metrics::notification_attempt(true);
+    metrics::notification_attempt(false);
+    metrics::change_detected("asn");
+
+    let output = metrics::render();
+    assert!(output.contains("gluetun_monitor_vpn_healthy 1"));
+    assert!(output.contains("gluetun_monitor_asn_allowed 1"));
+    assert!(output.contains("gluetun_monitor_home_isp_leak 0"));
+    assert!(output.contains("gluetun_monitor_port_forwarded 54321"));
+    assert!(
+        output.contains("gluetun_monitor_lookup_duration_seconds_count{provider=\"gluetun\"} 1")
+    );
+    assert!(output.contains(
+        "gluetun_monitor_lookup_failures_total{kind=\"http_status\",provider=\"ifconfig\"} 1"
+    ));
+    assert!(output.contains("gluetun_monitor_notification_attempts_total 2"));
+    assert!(output.contains("gluetun_monitor_notification_failures_total 1"));
+    assert!(output.contains("gluetun_monitor_changes_total{field=\"asn\"} 1"));
+
+    // Disallowed ASN flips the status gauges
+    metrics::record_status(&lookup_result("AS99999", None), AsnVerdict::Unknown, false);
+    let output = metrics::render();
+    assert!(output.contains("gluetun_monitor_vpn_healthy 0"));
+    assert!(output.contains("gluetun_monitor_asn_allowed 0"));
+    assert!(output.contains("gluetun_monitor_port_forwarded 0"));
+    assert!(output.contains("gluetun_monitor_home_isp_leak 0"));
+
+    // Home ISP ASN raises the leak gauge
+    metrics::record_status(&lookup_result("AS7922", None), AsnVerdict::Denied, false);
+    let output = metrics::render();
+    assert!(output.contains("gluetun_monitor_home_isp_leak 1"));
+    assert!(output.contains("gluetun_monitor_vpn_healthy 0"));
+}
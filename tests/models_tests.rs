@@ -1,5 +1,11 @@
 // Models module tests
-use gluetun_monitor::models::{CheckResponse, LookupResult, StatusResponse};
+use gluetun_monitor::{
+    models::{
+        CheckResponse, DnsReport, DnsResolver, LookupAttempt, LookupError, LookupResult,
+        StatusResponse,
+    },
+    policy::{RuleResult, RuleStatus},
+};
 
 #[test]
 fn test_lookup_result_serialization() {
@@ -11,7 +17,7 @@ fn test_lookup_result_serialization() {
         city: Some("Amsterdam".to_string()),
         region: Some("North Holland".to_string()),
         port_forwarded: Some(54321),
-        error: None,
+        ..Default::default()
     };
 
     let json = serde_json::to_string(&result).unwrap();
@@ -23,14 +29,8 @@ fn test_lookup_result_serialization() {
 #[test]
 fn test_lookup_result_with_error() {
     let result = LookupResult {
-        ip: None,
-        asn: None,
-        org: None,
-        country: None,
-        city: None,
-        region: None,
-        port_forwarded: None,
         error: Some("Lookup failed".to_string()),
+        ..Default::default()
     };
 
     let json = serde_json::to_string(&result).unwrap();
@@ -45,21 +45,22 @@ fn test_status_response_serialization() {
         asn: Some("AS12345".to_string()),
         org: Some("Test Org".to_string()),
         country: Some("Netherlands".to_string()),
-        city: None,
-        region: None,
-        port_forwarded: None,
-        error: None,
+        ..Default::default()
     };
 
     let response = StatusResponse {
         lookup,
         allowed_asns: vec!["AS12345".to_string(), "AS67890".to_string()],
+        denied_asns: Vec::new(),
+        allowed_cidrs: Vec::new(),
         configured: true,
     };
 
     let json = serde_json::to_string(&response).unwrap();
     assert!(json.contains("\"ip\":\"1.2.3.4\""));
     assert!(json.contains("\"allowed_asns\""));
+    assert!(!json.contains("\"denied_asns\"")); // Should be omitted when empty
+    assert!(!json.contains("\"allowed_cidrs\""));
     assert!(json.contains("\"configured\":true"));
 }
 
@@ -68,17 +69,14 @@ fn test_check_response_ok() {
     let lookup = LookupResult {
         ip: Some("1.2.3.4".to_string()),
         asn: Some("AS12345".to_string()),
-        org: None,
-        country: None,
-        city: None,
-        region: None,
-        port_forwarded: None,
-        error: None,
+        ..Default::default()
     };
 
     let response = CheckResponse {
         ok: true,
+        status: RuleStatus::Pass,
         reason: None,
+        rules: Vec::new(),
         lookup,
     };
 
@@ -92,21 +90,95 @@ fn test_check_response_not_ok() {
     let lookup = LookupResult {
         ip: Some("5.6.7.8".to_string()),
         asn: Some("AS99999".to_string()),
-        org: None,
-        country: None,
-        city: None,
-        region: None,
-        port_forwarded: None,
-        error: None,
+        ..Default::default()
     };
 
     let response = CheckResponse {
         ok: false,
+        status: RuleStatus::Fail,
         reason: Some("ASN not allowed".to_string()),
+        rules: vec![RuleResult {
+            rule: "asn_allow".to_string(),
+            status: RuleStatus::Fail,
+            message: "ASN not allowed".to_string(),
+        }],
         lookup,
     };
 
     let json = serde_json::to_string(&response).unwrap();
     assert!(json.contains("\"ok\":false"));
     assert!(json.contains("\"reason\":\"ASN not allowed\""));
+    assert!(json.contains("\"status\":\"fail\""));
+    assert!(json.contains("\"rules\":[{\"rule\":\"asn_allow\",\"status\":\"fail\""));
+}
+
+#[test]
+fn test_lookup_attempts_serialization() {
+    let result = LookupResult {
+        error: Some("ASN lookup failed".to_string()),
+        attempts: vec![
+            LookupAttempt {
+                provider: "gluetun".to_string(),
+                error: LookupError::Timeout,
+            },
+            LookupAttempt {
+                provider: "ifconfig".to_string(),
+                error: LookupError::HttpStatus { status: 429 },
+            },
+        ],
+        ..Default::default()
+    };
+
+    let json = serde_json::to_string(&result).unwrap();
+    assert!(json.contains(r#"{"provider":"gluetun","error":{"kind":"timeout"}}"#));
+    assert!(json.contains(r#""error":{"kind":"http_status","status":429}"#));
+}
+
+#[test]
+fn test_lookup_result_source_serialization() {
+    let checked_at = chrono::DateTime::parse_from_rfc3339("2026-01-15T10:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let result = LookupResult {
+        ip: Some("1.2.3.4".to_string()),
+        asn: Some("AS12345".to_string()),
+        source: Some("gluetun".to_string()),
+        latency_ms: Some(42),
+        checked_at: Some(checked_at),
+        ..Default::default()
+    };
+
+    let json = serde_json::to_string(&result).unwrap();
+    assert!(json.contains("\"source\":\"gluetun\""));
+    assert!(json.contains("\"latency_ms\":42"));
+    assert!(json.contains("\"checked_at\":\"2026-01-15T10:00:00Z\""));
+    assert!(!json.contains("\"attempts\""));
+    assert!(!result.is_fallback());
+}
+
+#[test]
+fn test_dns_resolver_networks() {
+    let resolver = |ip: &str, asn: Option<&str>| DnsResolver {
+        ip: ip.to_string(),
+        asn: asn.map(str::to_string),
+        org: None,
+    };
+    let report = DnsReport {
+        server: None,
+        resolvers: vec![
+            resolver("172.253.1.7", Some("AS15169")),
+            resolver("172.253.9.130", Some("AS15169")),
+            resolver("10.64.0.9", None),
+            resolver("10.64.0.1", None),
+            resolver("2a07:b944::2:1", None),
+        ],
+        names: Vec::new(),
+        error: None,
+    };
+
+    // Anycast addresses of one resolver collapse into its ASN or network
+    assert_eq!(
+        report.resolver_networks(),
+        vec!["10.64.0.0/24", "2a07:b944::/48", "AS15169"]
+    );
 }
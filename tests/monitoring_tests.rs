@@ -0,0 +1,126 @@
+// Monitoring module tests
+use gluetun_monitor::{
+    allowlist::AllowList,
+    ip_lookup::LookupCache,
+    models::{AppState, LookupResult},
+    monitoring::{reload_allowlist, AsnObserver},
+    policy::Policy,
+};
+use std::{
+    collections::HashSet,
+    env, fs,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+fn create_state(asns: &[&str]) -> AppState {
+    let allowed: HashSet<String> = asns.iter().map(|s| s.to_string()).collect();
+
+    AppState {
+        allowed_asns: Arc::new(AllowList::new(allowed)),
+        policy: Arc::new(Policy::default()),
+        client: reqwest::Client::new(),
+        ntfy_urls: Arc::new(Vec::new()),
+        gluetun_url: None,
+        gluetun_api_key: None,
+        providers: Arc::new(Vec::new()),
+        lookup_cache: Arc::new(LookupCache::new(Duration::from_secs(30))),
+        effective_config: Arc::new(RwLock::new(serde_json::json!({ "vpn": {} }))),
+        admin_token: None,
+        admin_state_file: None,
+        asn_observer: None,
+    }
+}
+
+#[tokio::test]
+async fn test_reload_allowlist_from_config_file() {
+    let path = env::temp_dir().join(format!(
+        "gluetun-monitor-reload-{}.toml",
+        std::process::id()
+    ));
+    fs::write(&path, "[vpn]\nallowed_asns = [\"AS2\", \"AS3\"]\n").unwrap();
+    let state = create_state(&["AS1", "AS2"]);
+
+    let diff = reload_allowlist(&state, Some(&path)).await.unwrap();
+
+    assert_eq!(diff, "added AS3; removed AS1");
+    assert_eq!(state.allowed_asns.sorted(), vec!["AS2", "AS3"]);
+    let effective = state.effective_config.read().unwrap().clone();
+    assert_eq!(
+        effective["vpn"]["allowed_asns"],
+        serde_json::json!(["AS2", "AS3"])
+    );
+
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_reload_rejects_invalid_config() {
+    let path = env::temp_dir().join(format!(
+        "gluetun-monitor-reload-invalid-{}.toml",
+        std::process::id()
+    ));
+    fs::write(&path, "[vpn]\nallowed_asns = [\"12345\"]\n").unwrap();
+    let state = create_state(&["AS1"]);
+
+    let err = reload_allowlist(&state, Some(&path)).await.unwrap_err();
+
+    assert!(err.contains("did you mean AS12345?"));
+    // Current allowlist is kept
+    assert_eq!(state.allowed_asns.sorted(), vec!["AS1"]);
+
+    fs::remove_file(path).unwrap();
+}
+
+fn observation(asn: &str, org: &str, checked_at: chrono::DateTime<chrono::Utc>) -> LookupResult {
+    serde_json::from_value(serde_json::json!({
+        "asn": asn,
+        "org": org,
+        "checked_at": checked_at,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_asn_observer_records_counts_and_times() {
+    let observer = AsnObserver::new(24);
+    let earlier = chrono::Utc::now() - chrono::Duration::hours(2);
+    let now = chrono::Utc::now();
+
+    observer.record(&observation("AS1", "Old Name", earlier));
+    observer.record(&observation("AS1", "New Name", now));
+    observer.record(&observation("AS2", "Other", now));
+
+    let observed = observer.observed();
+    assert_eq!(observed.len(), 2);
+
+    // Most frequent first
+    assert_eq!(observed[0].asn, "AS1");
+    assert_eq!(observed[0].count, 2);
+    assert_eq!(observed[0].first_seen, earlier);
+    assert_eq!(observed[0].last_seen, now);
+    assert_eq!(observed[0].org.as_deref(), Some("New Name"));
+    assert_eq!(observed[1].asn, "AS2");
+}
+
+#[test]
+fn test_asn_observer_forgets_outside_window() {
+    let observer = AsnObserver::new(1);
+    let stale = chrono::Utc::now() - chrono::Duration::hours(3);
+
+    observer.record(&observation("AS1", "Stale", stale));
+    observer.record(&observation("AS2", "Fresh", chrono::Utc::now()));
+
+    let asns: Vec<String> = observer.observed().into_iter().map(|o| o.asn).collect();
+    assert_eq!(asns, vec!["AS2"]);
+    assert_eq!(observer.window_hours(), 1);
+}
+
+#[test]
+fn test_asn_observer_huge_window() {
+    // Clamped instead of overflowing chrono
+    let observer = AsnObserver::new(u64::MAX);
+    observer.record(&observation("AS1", "Old", chrono::DateTime::UNIX_EPOCH));
+
+    assert_eq!(observer.observed().len(), 1);
+}
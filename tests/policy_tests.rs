@@ -0,0 +1,679 @@
+// Policy module tests
+use gluetun_monitor::{
+    dns::DnsAllowList,
+    geofence::{Geofence, LocationRule},
+    ip_lookup::AsnDatabase,
+    models::LookupResult,
+    policy::{parse_cidr, parse_rules, Policy, Rule, RuleKind, RuleStatus},
+};
+use std::{collections::HashSet, sync::Arc};
+
+fn lookup(value: serde_json::Value) -> LookupResult {
+    serde_json::from_value(value).unwrap()
+}
+
+fn allowed() -> HashSet<String> {
+    HashSet::from(["AS12345".to_string()])
+}
+
+fn specs(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+/// Status of each rule, in order
+fn statuses(policy: &Policy, info: &LookupResult) -> Vec<(String, RuleStatus)> {
+    policy
+        .evaluate(info, &allowed())
+        .results
+        .into_iter()
+        .map(|r| (r.rule, r.status))
+        .collect()
+}
+
+#[test]
+fn test_parse_rules() {
+    let rules = parse_rules(&specs(&["asn_allow", "Port_Forward:warn", "ip_cidr:fail"])).unwrap();
+    assert_eq!(
+        rules,
+        vec![
+            Rule::new(RuleKind::AsnAllow),
+            Rule {
+                kind: RuleKind::PortForward,
+                on_failure: RuleStatus::Warn,
+            },
+            Rule::new(RuleKind::IpCidr),
+        ]
+    );
+    assert_eq!(rules[1].to_string(), "port_forward:warn");
+
+    assert!(parse_rules(&specs(&["nope"])).unwrap_err().contains("nope"));
+    assert!(parse_rules(&specs(&["asn_allow:maybe"])).is_err());
+    assert!(parse_rules(&specs(&["asn_allow", "asn_allow:warn"]))
+        .unwrap_err()
+        .contains("more than once"));
+}
+
+#[test]
+fn test_parse_cidr() {
+    assert_eq!(parse_cidr("10.0.0.0/8").unwrap().to_string(), "10.0.0.0/8");
+    assert_eq!(parse_cidr(" 1.2.3.4 ").unwrap().to_string(), "1.2.3.4/32");
+    assert_eq!(
+        parse_cidr("2001:db8::/32").unwrap().to_string(),
+        "2001:db8::/32"
+    );
+    assert!(parse_cidr("10.0.0.0/33").is_err());
+}
+
+#[test]
+fn test_default_rules_follow_settings() {
+    let names =
+        |policy: &Policy| -> Vec<String> { policy.rules.iter().map(ToString::to_string).collect() };
+
+    assert_eq!(names(&Policy::default()), vec!["lookup", "asn_allow"]);
+
+    let policy = Policy::new(
+        HashSet::from(["AS7922".to_string()]),
+        Geofence {
+            countries: LocationRule::countries(vec!["Netherlands".to_string()], Vec::new()),
+            ..Geofence::default()
+        },
+        vec![parse_cidr("10.0.0.0/8").unwrap()],
+    );
+    assert_eq!(
+        names(&policy),
+        vec!["lookup", "asn_deny", "asn_allow", "location", "ip_cidr"]
+    );
+
+    // lookup is always evaluated first
+    let policy = Policy::default().with_rules(parse_rules(&specs(&["port_forward"])).unwrap());
+    assert_eq!(names(&policy), vec!["lookup", "port_forward"]);
+}
+
+#[test]
+fn test_evaluate_rules() {
+    let policy = Policy::new(
+        HashSet::from(["AS7922".to_string()]),
+        Geofence::default(),
+        vec![parse_cidr("1.2.3.0/24").unwrap()],
+    )
+    .with_rules(
+        parse_rules(&specs(&[
+            "asn_deny",
+            "asn_allow",
+            "port_forward",
+            "gluetun_source:warn",
+            "ip_cidr",
+        ]))
+        .unwrap(),
+    );
+
+    let healthy = lookup(serde_json::json!({
+        "ip": "1.2.3.4",
+        "asn": "AS12345",
+        "port_forwarded": 54321,
+        "source": "gluetun",
+    }));
+    let report = policy.evaluate(&healthy, &allowed());
+    assert_eq!(report.status, RuleStatus::Pass);
+    assert!(report.passed());
+    assert_eq!(report.reason(), None);
+
+    let leaking = lookup(serde_json::json!({
+        "ip": "5.6.7.8",
+        "asn": "AS7922",
+        "source": "ifconfig",
+    }));
+    assert_eq!(
+        statuses(&policy, &leaking),
+        vec![
+            ("lookup".to_string(), RuleStatus::Pass),
+            ("asn_deny".to_string(), RuleStatus::Fail),
+            ("asn_allow".to_string(), RuleStatus::Fail),
+            ("port_forward".to_string(), RuleStatus::Fail),
+            ("gluetun_source".to_string(), RuleStatus::Warn),
+            ("ip_cidr".to_string(), RuleStatus::Fail),
+        ]
+    );
+    let report = policy.evaluate(&leaking, &allowed());
+    assert!(!report.passed());
+    assert_eq!(
+        report.reason().unwrap(),
+        "traffic leaking via home ISP; ASN not allowed; no forwarded port; IP 5.6.7.8 is not in VPN_ALLOWED_CIDRS"
+    );
+}
+
+#[test]
+fn test_evaluate_fallback_and_failed_lookup() {
+    let policy = Policy::default();
+
+    let fallback = lookup(serde_json::json!({
+        "ip": "1.2.3.4",
+        "asn": "AS12345",
+        "source": "ipapi",
+        "attempts": [{ "provider": "gluetun", "error": { "kind": "timeout" } }],
+    }));
+    let report = policy.evaluate(&fallback, &allowed());
+    assert_eq!(report.status, RuleStatus::Warn);
+    assert!(report.passed());
+    assert_eq!(
+        report.reason().unwrap(),
+        "answered by fallback provider ipapi after gluetun failed"
+    );
+
+    let failed = lookup(serde_json::json!({ "error": "ASN lookup failed" }));
+    assert_eq!(
+        statuses(&policy, &failed),
+        vec![
+            ("lookup".to_string(), RuleStatus::Fail),
+            ("asn_allow".to_string(), RuleStatus::Skip),
+        ]
+    );
+    assert_eq!(
+        policy.evaluate(&failed, &allowed()).reason().unwrap(),
+        "ASN lookup failed"
+    );
+
+    // A broken proxy is named instead of the provider errors behind it
+    let failed = lookup(serde_json::json!({
+        "error": "All IP lookup methods failed",
+        "proxy": { "url": "http://gluetun:8888", "reachable": false, "error": "Connection refused" }
+    }));
+    assert_eq!(
+        policy.evaluate(&failed, &allowed()).reason().unwrap(),
+        "lookup proxy http://gluetun:8888 unreachable: Connection refused"
+    );
+
+    // Leaks found outside the provider lookup still show when it failed
+    let independent = Policy::default().with_rules(vec![
+        Rule::new(RuleKind::Lookup),
+        Rule::new(RuleKind::AsnAllow),
+        Rule::new(RuleKind::KillSwitch),
+        Rule::new(RuleKind::DnsLeak),
+        Rule::new(RuleKind::Ipv6Leak),
+    ]);
+    let failed = lookup(serde_json::json!({
+        "error": "All IP lookup methods failed",
+        "kill_switch": { "exit_ip": "203.0.113.7", "home_count": 1, "leaking": true },
+        "dns": { "resolvers": [{ "ip": "10.64.0.1" }] },
+        "ipv6": { "ip": "2601::1", "asn": "AS7922" }
+    }));
+    assert_eq!(
+        statuses(&independent, &failed),
+        vec![
+            ("lookup".to_string(), RuleStatus::Fail),
+            ("asn_allow".to_string(), RuleStatus::Skip),
+            ("kill_switch".to_string(), RuleStatus::Fail),
+            ("dns_leak".to_string(), RuleStatus::Fail),
+            ("ipv6_leak".to_string(), RuleStatus::Fail),
+        ]
+    );
+
+    // An empty allowlist fails closed
+    let report = policy.evaluate(&fallback, &HashSet::new());
+    assert_eq!(report.reason().unwrap(), "VPN_ALLOWED_ASNS not set");
+}
+
+#[test]
+fn test_ip_allowed_ipv4_and_ipv6() {
+    let policy = Policy::new(
+        HashSet::new(),
+        Geofence::default(),
+        vec![
+            parse_cidr("185.159.156.0/22").unwrap(),
+            parse_cidr("2a07:b944::/32").unwrap(),
+        ],
+    );
+    let at = |ip: &str| lookup(serde_json::json!({ "ip": ip }));
+
+    assert_eq!(policy.ip_allowed(&at("185.159.157.10")), Some(true));
+    assert_eq!(policy.ip_allowed(&at("2a07:b944::2:1")), Some(true));
+    assert_eq!(policy.ip_allowed(&at("::ffff:185.159.157.10")), Some(true));
+    assert_eq!(policy.ip_allowed(&at("81.2.69.160")), Some(false));
+    assert_eq!(policy.ip_allowed(&at("not-an-ip")), Some(false));
+    assert_eq!(Policy::default().ip_allowed(&at("81.2.69.160")), None);
+
+    // Gluetun may answer without an ASN, the IP range still validates it
+    let report = policy.evaluate(&at("185.159.157.10"), &HashSet::new());
+    let ip_cidr = report.results.iter().find(|r| r.rule == "ip_cidr").unwrap();
+    assert_eq!(ip_cidr.status, RuleStatus::Pass);
+    assert_eq!(ip_cidr.message, "IP 185.159.157.10 is in VPN_ALLOWED_CIDRS");
+}
+
+#[test]
+fn test_asn_crosscheck() {
+    let mut policy =
+        Policy::default().with_rules(parse_rules(&specs(&["asn_crosscheck"])).unwrap());
+    policy.asn_database = Some(Arc::new(
+        AsnDatabase::from_tsv("185.159.156.0\t185.159.159.255\t209103\tCH\tProton AG\n").unwrap(),
+    ));
+    let crosscheck = |value: serde_json::Value| {
+        let report = policy.evaluate(&lookup(value), &allowed());
+        let result = report.results.into_iter().last().unwrap();
+        (result.status, result.message)
+    };
+
+    assert_eq!(
+        crosscheck(serde_json::json!({ "ip": "185.159.157.10", "asn": "AS209103" })),
+        (
+            RuleStatus::Pass,
+            "ASN AS209103 matches the local ASN database".to_string()
+        )
+    );
+    assert_eq!(
+        crosscheck(serde_json::json!({
+            "ip": "185.159.157.10",
+            "asn": "AS12345",
+            "source": "gluetun",
+        })),
+        (
+            RuleStatus::Fail,
+            "gluetun reported AS12345, local ASN database says AS209103".to_string()
+        )
+    );
+    assert_eq!(
+        crosscheck(serde_json::json!({ "ip": "8.8.8.8", "asn": "AS15169" })).0,
+        RuleStatus::Warn
+    );
+}
+
+#[test]
+fn test_consensus() {
+    let policy = Policy::default().with_rules(vec![Rule::new(RuleKind::Consensus)]);
+    let check = |value: serde_json::Value| {
+        let result = policy
+            .evaluate(&lookup(value), &allowed())
+            .results
+            .remove(1);
+        (result.status, result.message)
+    };
+    let answer = |provider: &str, ip: &str| serde_json::json!({ "provider": provider, "ip": ip });
+
+    assert_eq!(
+        check(serde_json::json!({
+            "ip": "1.2.3.4",
+            "consensus": {
+                "agreed": true,
+                "answers": [answer("gluetun", "1.2.3.4"), answer("ifconfig", "1.2.3.4")],
+            },
+        })),
+        (
+            RuleStatus::Pass,
+            "2 providers agree on IP and ASN".to_string()
+        )
+    );
+    assert_eq!(
+        check(serde_json::json!({
+            "ip": "1.2.3.4",
+            "consensus": {
+                "agreed": false,
+                "answers": [answer("gluetun", "1.2.3.4"), answer("ifconfig", "5.6.7.8")],
+                "disagreements": ["ifconfig saw IP 5.6.7.8, gluetun saw 1.2.3.4"],
+            },
+        })),
+        (
+            RuleStatus::Fail,
+            "providers disagree: ifconfig saw IP 5.6.7.8, gluetun saw 1.2.3.4".to_string()
+        )
+    );
+    assert_eq!(
+        check(serde_json::json!({
+            "ip": "1.2.3.4",
+            "consensus": {
+                "agreed": true,
+                "answers": [
+                    answer("gluetun", "1.2.3.4"),
+                    { "provider": "ifconfig", "error": { "kind": "timeout" } },
+                ],
+            },
+        })),
+        (
+            RuleStatus::Warn,
+            "only 1 of 2 providers answered, nothing to compare".to_string()
+        )
+    );
+    assert_eq!(
+        check(serde_json::json!({ "ip": "1.2.3.4" })),
+        (RuleStatus::Fail, "consensus mode not enabled".to_string())
+    );
+}
+
+#[test]
+fn test_ipv6_leak() {
+    let mut policy = Policy::new(
+        HashSet::from(["AS7922".to_string()]),
+        Geofence::default(),
+        vec![parse_cidr("2a07:b944::/32").unwrap()],
+    )
+    .with_rules(vec![Rule::new(RuleKind::Ipv6Leak)]);
+    let check = |policy: &Policy, ipv6: serde_json::Value| {
+        let info = lookup(serde_json::json!({ "ip": "1.2.3.4", "asn": "AS12345", "ipv6": ipv6 }));
+        let result = policy.evaluate(&info, &allowed()).results.remove(1);
+        (result.status, result.message)
+    };
+
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "error": "IPv6 not reachable" })
+        ),
+        (RuleStatus::Pass, "IPv6 is not reachable".to_string())
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "ip": "2a07:b944::2", "asn": "AS9009" })
+        ),
+        (
+            RuleStatus::Pass,
+            "IPv6 2a07:b944::2 is in VPN_ALLOWED_CIDRS".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "ip": "2001:db8::1", "asn": "AS12345" })
+        ),
+        (
+            RuleStatus::Pass,
+            "IPv6 2001:db8::1 exits via allowed ASN AS12345".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "ip": "2601::1", "asn": "AS7922" })
+        ),
+        (
+            RuleStatus::Fail,
+            "IPv6 traffic leaking via home ISP (2601::1 on AS7922)".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "ip": "2001:db8::1", "asn": "AS9009" })
+        ),
+        (
+            RuleStatus::Fail,
+            "IPv6 2001:db8::1 is reachable via ASN AS9009, which is not allowed".to_string()
+        )
+    );
+
+    // Without dual-stack lookups there is nothing to check
+    let info = lookup(serde_json::json!({ "ip": "1.2.3.4", "asn": "AS12345" }));
+    policy.rules = vec![Rule::new(RuleKind::Ipv6Leak)];
+    assert_eq!(
+        policy.evaluate(&info, &allowed()).results[0].message,
+        "dual-stack lookups not enabled"
+    );
+}
+
+#[test]
+fn test_dns_leak() {
+    let mut policy = Policy::new(
+        HashSet::from(["AS7922".to_string()]),
+        Geofence::default(),
+        Vec::new(),
+    )
+    .with_rules(vec![Rule::new(RuleKind::DnsLeak)]);
+    let check = |policy: &Policy, dns: serde_json::Value| {
+        let info = lookup(serde_json::json!({ "ip": "1.2.3.4", "asn": "AS12345", "dns": dns }));
+        let result = policy.evaluate(&info, &allowed()).results.remove(1);
+        (result.status, result.message)
+    };
+
+    // Without an allow-list the resolvers must be on VPN_ALLOWED_ASNS
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "resolvers": [{ "ip": "10.2.0.1", "asn": "AS12345" }] })
+        ),
+        (
+            RuleStatus::Pass,
+            "DNS resolvers 10.2.0.1 are allowed".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "resolvers": [{ "ip": "75.75.75.75", "asn": "AS7922" }] })
+        ),
+        (
+            RuleStatus::Fail,
+            "DNS leaking via home ISP resolver 75.75.75.75 (AS7922)".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "resolvers": [{ "ip": "8.8.8.8" }] })
+        ),
+        (
+            RuleStatus::Fail,
+            "DNS resolver 8.8.8.8 is not allowed (ASN unknown)".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "error": "whoami.akamai.net: timed out" })
+        ),
+        (
+            RuleStatus::Fail,
+            "DNS probe failed: whoami.akamai.net: timed out".to_string()
+        )
+    );
+
+    // An allow-list replaces VPN_ALLOWED_ASNS
+    policy.dns_allowed = DnsAllowList {
+        resolvers: vec![parse_cidr("10.64.0.0/10").unwrap()],
+        asns: HashSet::from(["AS15169".to_string()]),
+    };
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({
+                "resolvers": [{ "ip": "10.64.0.1" }, { "ip": "8.8.8.8", "asn": "AS15169" }],
+                "names": [{ "name": "example.com", "error": "NXDOMAIN" }]
+            })
+        ),
+        (
+            RuleStatus::Warn,
+            "DNS resolvers 10.64.0.1, 8.8.8.8 are allowed, but example.com failed to resolve"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "resolvers": [{ "ip": "10.2.0.1", "asn": "AS12345" }] })
+        ),
+        (
+            RuleStatus::Fail,
+            "DNS resolver 10.2.0.1 (AS12345) is not allowed".to_string()
+        )
+    );
+
+    // Without the probe there is nothing to check
+    let info = lookup(serde_json::json!({ "ip": "1.2.3.4", "asn": "AS12345" }));
+    policy.rules = vec![Rule::new(RuleKind::DnsLeak)];
+    assert_eq!(
+        policy.evaluate(&info, &allowed()).results[0].message,
+        "DNS probe not enabled"
+    );
+}
+
+#[test]
+fn test_kill_switch() {
+    let mut policy = Policy::default().with_rules(vec![Rule::new(RuleKind::KillSwitch)]);
+    let check = |policy: &Policy, kill_switch: serde_json::Value| {
+        let info = lookup(serde_json::json!({
+            "ip": "185.159.157.10",
+            "asn": "AS12345",
+            "kill_switch": kill_switch
+        }));
+        let result = policy.evaluate(&info, &allowed()).results.remove(1);
+        (result.status, result.message)
+    };
+    let probe = |outcome: &str, message: &str| {
+        serde_json::json!({
+            "checked_at": "2026-10-17T03:00:00Z",
+            "outcome": outcome,
+            "message": message
+        })
+    };
+
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "exit_ip": "185.159.157.10", "home_count": 1, "leaking": false })
+        ),
+        (
+            RuleStatus::Pass,
+            "exit IP 185.159.157.10 is not a home IP".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({
+                "exit_ip": "185.159.157.10",
+                "home_count": 1,
+                "leaking": false,
+                "probe": probe("held", "probe unreachable")
+            })
+        ),
+        (
+            RuleStatus::Pass,
+            "exit IP 185.159.157.10 is not a home IP, kill switch held at 2026-10-17 03:00:00 UTC"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({ "exit_ip": "203.0.113.7", "home_count": 1, "leaking": true })
+        ),
+        (
+            RuleStatus::Fail,
+            "VPN exit IP 203.0.113.7 is the home IP, traffic bypasses the tunnel".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({
+                "exit_ip": "185.159.157.10",
+                "leaking": false,
+                "probe": probe("leaked", "probe answered HTTP 200 with the tunnel stopped")
+            })
+        ),
+        (
+            RuleStatus::Fail,
+            "kill switch did not hold: probe answered HTTP 200 with the tunnel stopped".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({
+                "exit_ip": "185.159.157.10",
+                "leaking": false,
+                "probe": probe("error", "failed to stop the VPN: HTTP status 401")
+            })
+        ),
+        (
+            RuleStatus::Warn,
+            "kill switch probe failed: failed to stop the VPN: HTTP status 401".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            &policy,
+            serde_json::json!({
+                "home_count": 1,
+                "leaking": false,
+                "error": "Gluetun exit lookup failed: HTTP status 500"
+            })
+        ),
+        (
+            RuleStatus::Warn,
+            "Gluetun exit lookup failed: HTTP status 500".to_string()
+        )
+    );
+
+    // Without kill switch verification there is nothing to check
+    let info = lookup(serde_json::json!({ "ip": "1.2.3.4", "asn": "AS12345" }));
+    policy.rules = vec![Rule::new(RuleKind::KillSwitch)];
+    assert_eq!(
+        policy.evaluate(&info, &allowed()).results[0].message,
+        "kill switch verification not enabled"
+    );
+}
+
+#[test]
+fn test_tunnel() {
+    let policy = Policy::default().with_rules(vec![Rule::new(RuleKind::Tunnel)]);
+    let check = |info: serde_json::Value| {
+        let result = policy.evaluate(&lookup(info), &allowed()).results.remove(1);
+        (result.status, result.message)
+    };
+
+    assert_eq!(
+        check(serde_json::json!({ "ip": "185.159.157.10", "tunnel": { "state": "running" } })),
+        (RuleStatus::Pass, "VPN tunnel is running".to_string())
+    );
+    // A cached allowed IP does not hide a stopped tunnel
+    let (status, message) = check(serde_json::json!({
+        "ip": "185.159.157.10",
+        "asn": "AS12345",
+        "cache_age_secs": 42,
+        "tunnel": { "state": "stopped" }
+    }));
+    assert_eq!(status, RuleStatus::Fail);
+    assert_eq!(
+        message,
+        "VPN tunnel is stopped, exit IP 185.159.157.10 is from a lookup 42s ago"
+    );
+    // Evaluated even when the lookup failed
+    assert_eq!(
+        check(
+            serde_json::json!({ "error": "All IP lookup services failed", "tunnel": { "state": "crashed" } })
+        ),
+        (RuleStatus::Fail, "VPN tunnel is crashed".to_string())
+    );
+    // Stopped on purpose by the kill switch probe, or on its way up
+    assert_eq!(
+        check(
+            serde_json::json!({ "ip": "185.159.157.10", "tunnel": { "state": "stopped", "probing": true } })
+        ),
+        (
+            RuleStatus::Warn,
+            "VPN tunnel is stopped during the kill switch probe".to_string()
+        )
+    );
+    assert_eq!(
+        check(serde_json::json!({ "ip": "185.159.157.10", "tunnel": { "state": "starting" } })),
+        (RuleStatus::Warn, "VPN tunnel is starting".to_string())
+    );
+    assert_eq!(
+        check(serde_json::json!({ "ip": "185.159.157.10", "tunnel": { "state": "reconnecting" } })),
+        (
+            RuleStatus::Warn,
+            "Gluetun reported an unknown tunnel state".to_string()
+        )
+    );
+    assert_eq!(
+        check(
+            serde_json::json!({ "ip": "185.159.157.10", "tunnel": { "error": "HTTP status 401" } })
+        ),
+        (
+            RuleStatus::Warn,
+            "tunnel state unknown: HTTP status 401".to_string()
+        )
+    );
+    assert_eq!(
+        check(serde_json::json!({ "ip": "185.159.157.10" })).0,
+        RuleStatus::Fail
+    );
+}